@@ -4,6 +4,263 @@ lalrpop_mod!(#[allow(clippy::all)] pub grammar, "/ast/grammar.rs");
 
 pub use structure::*;
 
-pub fn ast_pass(input: &'_ str) -> Result<File, String> {
-    Ok(grammar::FileParser::new().parse(input).unwrap())
+use {
+    crate::diagnostic::Span,
+    lalrpop_util::{lexer::Token, ParseError},
+    std::{convert::TryFrom, fmt},
+    thiserror::Error,
+};
+
+/// `file` is where the input is in the source map, every span points into it.
+/// Each syntax error is pushed to `errors`, there's still a file unless one couldn't be recovered from
+pub fn ast_pass(input: &'_ str, file: usize, errors: &mut Vec<SyntaxError>) -> Option<File> {
+    match grammar::FileParser::new().parse(file, errors, input) {
+        Ok(ast) => Some(ast),
+        Err(error) => {
+            errors.push(SyntaxError::from_parse(error, file));
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("{message}")]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl SyntaxError {
+    pub fn user(message: &str, span: Span) -> Self {
+        SyntaxError {
+            message: message.to_string(),
+            span,
+        }
+    }
+
+    fn from_parse(error: ParseError<usize, Token, SyntaxError>, file: usize) -> Self {
+        let (message, start, end) = match error {
+            ParseError::InvalidToken { location } => {
+                ("Unrecognized token".to_string(), location, location)
+            }
+            ParseError::UnrecognizedEOF { location, expected } => (
+                format!("Unexpected end of file, expected {}", one_of(&expected)),
+                location,
+                location,
+            ),
+            ParseError::UnrecognizedToken {
+                token: (start, token, end),
+                expected,
+            } => (
+                format!("Unexpected `{}`, expected {}", token, one_of(&expected)),
+                start,
+                end,
+            ),
+            ParseError::ExtraToken {
+                token: (start, token, end),
+            } => (format!("Unexpected `{}`", token), start, end),
+            ParseError::User { error } => return error,
+        };
+
+        SyntaxError {
+            message,
+            span: Span::new(file, start, end),
+        }
+    }
+}
+
+/// LALRPOP gives the tokens quoted already, regexes are named like `IDENTIFIER`,
+/// past a handful there are too many to be worth listing
+fn one_of(expected: &[String]) -> String {
+    match expected {
+        [] => "nothing".to_string(),
+        [one] => one.clone(),
+        _ if expected.len() > 8 => format!("one of {} possible tokens", expected.len()),
+        [rest @ .., last] => format!("one of {} or {}", rest.join(", "), last),
+    }
+}
+
+/// The bytes of a string literal, without its quotes and with escapes replaced
+pub fn unescape(literal: &str) -> Result<Vec<u8>, &'static str> {
+    let mut bytes = Vec::with_capacity(literal.len());
+    let mut chars = literal.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        let byte = match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => b'\0',
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('\'') => b'\'',
+            Some('x') => {
+                let digits = chars
+                    .as_str()
+                    .get(..2)
+                    .ok_or("Expected two hex digits after `\\x`")?;
+                let byte = u8::from_str_radix(digits, 16)
+                    .map_err(|_| "Expected two hex digits after `\\x`")?;
+                chars.nth(1);
+                byte
+            }
+            _ => return Err("Unknown escape in string literal"),
+        };
+        bytes.push(byte);
+    }
+
+    Ok(bytes)
+}
+
+/// What's wrong with a number literal, types are named the way they're written
+#[derive(Debug, Clone, Error)]
+pub enum LiteralError {
+    #[error("Malformed number literal `{literal}`")]
+    Malformed { literal: String },
+    #[error("Literal `{literal}` doesn't fit in {ty}, which goes from {min} to {max}")]
+    OutOfRange {
+        literal: String,
+        ty: &'static str,
+        min: String,
+        max: String,
+    },
+}
+
+/// `literal as ty`, integers can be hex `0x`, octal `0o` or binary `0b`,
+/// floats can have an exponent like `1e-9`, and `_` can go between any digits
+pub fn number_literal(literal: &str, ty: TyKind, span: Span) -> Result<Literal, SyntaxError> {
+    let checked = match ty {
+        TyKind::F32 | TyKind::F64 => float_literal(literal, ty),
+        _ => int_literal(literal, ty),
+    };
+    checked.map_err(|error| SyntaxError::user(&error.to_string(), span))
+}
+
+/// A bad literal is pushed to `errors` and parsing carries on past it
+pub fn literal_or_error(
+    literal: Result<Literal, SyntaxError>,
+    errors: &mut Vec<SyntaxError>,
+) -> ExpressionKind {
+    match literal {
+        Ok(literal) => ExpressionKind::Literal(literal),
+        Err(error) => {
+            errors.push(error);
+            ExpressionKind::Error
+        }
+    }
+}
+
+/// A string with a bad escape is pushed to `errors` too, `literal` is without its quotes
+pub fn string_or_error(
+    literal: &str,
+    nul_terminated: bool,
+    span: Span,
+    errors: &mut Vec<SyntaxError>,
+) -> ExpressionKind {
+    match unescape(literal) {
+        Ok(bytes) => ExpressionKind::StringLiteral {
+            bytes,
+            nul_terminated,
+        },
+        Err(message) => {
+            errors.push(SyntaxError::user(message, span));
+            ExpressionKind::Error
+        }
+    }
+}
+
+fn int_literal(literal: &str, ty: TyKind) -> Result<Literal, LiteralError> {
+    let (name, min, max) = match ty {
+        TyKind::I8 => ("I8", i8::MIN as i128, i8::MAX as i128),
+        TyKind::I16 => ("I16", i16::MIN as i128, i16::MAX as i128),
+        TyKind::I32 => ("I32", i32::MIN as i128, i32::MAX as i128),
+        TyKind::I64 => ("I64", i64::MIN as i128, i64::MAX as i128),
+        TyKind::ISize => ("ISize", isize::MIN as i128, isize::MAX as i128),
+        TyKind::U8 => ("U8", 0, u8::MAX as i128),
+        TyKind::U16 => ("U16", 0, u16::MAX as i128),
+        TyKind::U32 => ("U32", 0, u32::MAX as i128),
+        TyKind::U64 => ("U64", 0, u64::MAX as i128),
+        TyKind::USize => ("USize", 0, usize::MAX as i128),
+        _ => unreachable!("Only number types follow a number literal"),
+    };
+
+    let value = match int_value(literal)? {
+        Some(value) if (min..=max).contains(&value) => value,
+        _ => {
+            return Err(LiteralError::OutOfRange {
+                literal: literal.to_string(),
+                ty: name,
+                min: min.to_string(),
+                max: max.to_string(),
+            })
+        }
+    };
+
+    Ok(match ty {
+        TyKind::I8 => Literal::I8(value as i8),
+        TyKind::I16 => Literal::I16(value as i16),
+        TyKind::I32 => Literal::I32(value as i32),
+        TyKind::I64 => Literal::I64(value as i64),
+        TyKind::ISize => Literal::ISize(value as isize),
+        TyKind::U8 => Literal::U8(value as u8),
+        TyKind::U16 => Literal::U16(value as u16),
+        TyKind::U32 => Literal::U32(value as u32),
+        TyKind::U64 => Literal::U64(value as u64),
+        _ => Literal::USize(value as usize),
+    })
+}
+
+/// An integer written as a float is the nearest float to it
+fn float_literal(literal: &str, ty: TyKind) -> Result<Literal, LiteralError> {
+    // Only integers have a prefix, those are read like any other integer
+    let value = match literal.replace('_', "").parse::<f64>() {
+        Ok(value) => Some(value),
+        Err(_) => int_value(literal)?.map(|value| value as f64),
+    };
+
+    let out_of_range = |name, max: &dyn fmt::LowerExp| LiteralError::OutOfRange {
+        literal: literal.to_string(),
+        ty: name,
+        min: format!("-{:e}", max),
+        max: format!("{:e}", max),
+    };
+
+    match (ty, value) {
+        (TyKind::F32, Some(value)) if (value as f32).is_finite() => Ok(Literal::F32(value as f32)),
+        (TyKind::F32, _) => Err(out_of_range("F32", &f32::MAX)),
+        (_, Some(value)) if value.is_finite() => Ok(Literal::F64(value)),
+        _ => Err(out_of_range("F64", &f64::MAX)),
+    }
+}
+
+/// `None` when there are too many digits for even an `i128`
+fn int_value(literal: &str) -> Result<Option<i128>, LiteralError> {
+    let (negative, unsigned) = match literal.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x") => (16, &unsigned[2..]),
+        Some("0o") => (8, &unsigned[2..]),
+        Some("0b") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+
+    let digits = digits.replace('_', "");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(LiteralError::Malformed {
+            literal: literal.to_string(),
+        });
+    }
+
+    Ok(u128::from_str_radix(&digits, radix)
+        .ok()
+        .and_then(|value| i128::try_from(value).ok())
+        .map(|value| if negative { -value } else { value }))
 }
@@ -1,44 +1,198 @@
-#[derive(Debug, Clone)]
+use crate::diagnostic::Span;
+
+#[derive(Debug, Clone, Default)]
 pub struct File {
+    /// Filled in by the loader for imported files, even if they don't declare it
+    pub module: Option<Identifier>,
+    /// Where the file says which module it is, if it does
+    pub module_span: Option<Span>,
+    pub imports: Vec<Import>,
     pub items: Vec<Item>,
+    /// Written before items, each one knows the span of the item it's on
+    pub attributes: Vec<Attribute>,
+}
+
+/// `#[name(args, ...)]`, it applies to everything inside its item
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: Identifier,
+    pub args: Vec<Identifier>,
+    pub target: Span,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: Identifier,
+    /// Every public name in the module, rather than the module or one item
+    pub glob: bool,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum Item {
     Declare(DeclareBlock),
     Function(Function),
+    Struct(Struct),
+    Union(Union),
+    Const(Const),
+    Static(Static),
+    Impl(Impl),
+    Trait(Trait),
+}
+
+impl Item {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Declare(item) => item.span,
+            Self::Function(item) => item.span,
+            Self::Struct(item) => item.span,
+            Self::Union(item) => item.span,
+            Self::Const(item) => item.span,
+            Self::Static(item) => item.span,
+            Self::Impl(item) => item.span,
+            Self::Trait(item) => item.span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DeclareBlock {
-    pub functions: Vec<DeclareFunction>,
+    pub items: Vec<DeclareItem>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum DeclareItem {
+    Function(DeclareFunction),
+    Static(DeclareStatic),
 }
 
 #[derive(Debug, Clone)]
 pub struct DeclareFunction {
     pub name: Identifier,
     pub params: Vec<Parameter>,
+    /// Ends its params with `...`, taking any number of extra args like C's `printf`
+    pub variadic: bool,
     pub ty: Ty,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeclareStatic {
+    pub mutable: bool,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub scope: Scope,
     pub name: Identifier,
+    pub ty_params: Vec<TyParam>,
     pub params: Vec<Parameter>,
     pub ty: Ty,
     pub body: Block,
+    pub span: Span,
+}
+
+/// A type parameter of a function, each bound is a trait its type argument needs an impl of
+#[derive(Debug, Clone)]
+pub struct TyParam {
+    pub name: Identifier,
+    pub bounds: Vec<Identifier>,
+    pub span: Span,
+}
+
+/// Methods of a type, either its own or the ones a trait asks for,
+/// a generic type names its parameters after it
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub trait_: Option<Identifier>,
+    pub ty: Ty,
+    pub ty_params: Vec<Identifier>,
+    pub functions: Vec<Function>,
+    pub span: Span,
+}
+
+/// Methods every impl of the trait has to have, written like declared functions
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub scope: Scope,
+    pub name: Identifier,
+    pub methods: Vec<DeclareFunction>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub scope: Scope,
+    pub name: Identifier,
+    pub ty_params: Vec<Identifier>,
+    pub fields: Vec<Field>,
+    pub span: Span,
+}
+
+/// Worked out while compiling, every use is replaced by the value
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub scope: Scope,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub value: Expression,
+    pub span: Span,
+}
+
+/// Lives at one address for the whole program
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub scope: Scope,
+    pub mutable: bool,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub value: Expression,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: Identifier,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Union {
+    pub scope: Scope,
+    pub name: Identifier,
+    pub ty_params: Vec<Identifier>,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<Ty>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Parameter {
+    /// Only a function with a body can change its params, with `mut name: T`
+    pub mutable: bool,
     pub name: Identifier,
     pub ty: Ty,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum Scope {
+    /// Exported with its plain name, so C can call it too
     Public,
+    /// Other modules can import it, C can't see it
+    Shared,
     Local,
 }
 
@@ -60,7 +214,13 @@ impl Identifier {
 }
 
 #[derive(Debug, Clone)]
-pub enum Ty {
+pub struct Ty {
+    pub kind: TyKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum TyKind {
     I8,
     I16,
     I32,
@@ -73,17 +233,30 @@ pub enum Ty {
     USize,
     F32,
     F64,
+    Bool,
+    Unit,
+    Never,
+    Pointer { mutable: bool, to: Box<Ty> },
+    Array { of: Box<Ty>, len: u32 },
+    Slice { mutable: bool, of: Box<Ty> },
+    Func { params: Vec<Ty>, ret: Box<Ty> },
+    Dyn { mutable: bool, name: Identifier },
+    Named { name: Identifier, args: Vec<Ty> },
 }
 
 #[derive(Debug, Clone)]
 pub struct Block {
     pub body: Vec<Statement>,
     pub trailing: Option<Box<Expression>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     LetBinding {
+        span: Span,
+        /// `let mut`, otherwise the binding can't be changed after it's made
+        mutable: bool,
         place: Identifier,
         value: Expression,
         ty: Option<Ty>,
@@ -92,18 +265,31 @@ pub enum Statement {
 }
 
 #[derive(Debug, Clone)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     Literal(Literal),
     Lookup(Identifier),
     Block(Block),
+    /// `place = value`, or `place += value` and the like when there's an `operator`
     Assignment {
-        place: Identifier,
+        place: Box<Expression>,
+        operator: Option<InfixOperator>,
         value: Box<Expression>,
     },
     FuncCall {
         name: Identifier,
         args: Vec<Expression>,
     },
+    /// Calling anything that isn't just a name, like `handlers[i](event)`
+    Call {
+        callee: Box<Expression>,
+        args: Vec<Expression>,
+    },
     PrefixCall {
         operator: PrefixOperator,
         value: Box<Expression>,
@@ -118,6 +304,102 @@ pub enum Expression {
         when_true: Block,
         when_false: Option<Block>,
     },
+    Loop {
+        label: Option<Identifier>,
+        body: Block,
+    },
+    While {
+        label: Option<Identifier>,
+        predicate: Box<Expression>,
+        body: Block,
+    },
+    Break {
+        label: Option<Identifier>,
+        value: Option<Box<Expression>>,
+    },
+    Continue {
+        label: Option<Identifier>,
+    },
+    Return {
+        value: Option<Box<Expression>>,
+    },
+    AddressOf {
+        mutable: bool,
+        place: Box<Expression>,
+    },
+    Deref(Box<Expression>),
+    /// `"..."` is a `Str`, `b"..."` is a `*U8` ending in a NUL for C
+    StringLiteral {
+        bytes: Vec<u8>,
+        nul_terminated: bool,
+    },
+    Cast {
+        value: Box<Expression>,
+        /// Written `as!`, traps instead of saturating or wrapping when the number doesn't fit
+        trapping: bool,
+        ty: Ty,
+    },
+    StructLiteral {
+        name: Identifier,
+        fields: Vec<FieldInit>,
+    },
+    FieldAccess {
+        value: Box<Expression>,
+        field: Identifier,
+    },
+    ArrayLiteral {
+        values: Vec<Expression>,
+    },
+    /// Written `[value; len]`, the value is only worked out once
+    ArrayRepeat {
+        value: Box<Expression>,
+        len: u32,
+    },
+    Index {
+        value: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// Written `&value[start..end]`, either end can be left out
+    Slice {
+        mutable: bool,
+        value: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
+    VariantLiteral {
+        name: Identifier,
+        values: Vec<Expression>,
+    },
+    Match {
+        value: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+    /// Stands in for a statement or trailing expression that didn't parse, the error is already reported
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub value: Expression,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    /// `None` bindings skip that part of the payload
+    Variant {
+        name: Identifier,
+        bindings: Vec<Option<Identifier>>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    pub name: Identifier,
+    pub value: Expression,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -134,11 +416,16 @@ pub enum Literal {
     USize(usize),
     F32(f32),
     F64(f64),
+    Bool(bool),
+    /// Written `()`, also what a block without a trailing expression gives
+    Unit,
 }
 
 #[derive(Debug, Clone)]
 pub enum PrefixOperator {
     Negate,
+    BooleanNot,
+    BitNot,
 }
 
 #[derive(Debug, Clone)]
@@ -147,6 +434,13 @@ pub enum InfixOperator {
     Subtract,
     Multiply,
     Divide,
+    Remainder,
+
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 
     Equal,
     NotEqual,
@@ -154,4 +448,7 @@ pub enum InfixOperator {
     LessThan,
     GreaterOrEqual,
     LessOrEqual,
+
+    BooleanAnd,
+    BooleanOr,
 }
@@ -0,0 +1,511 @@
+pub use super::*;
+
+/// How a value crosses a function boundary, following the System V ABI
+pub enum PassMode {
+    /// Scalars fit in a single register
+    Direct(Type),
+    /// Structs up to 16 bytes are split into eightbytes, one register each
+    Split(Vec<Eightbyte>),
+    /// Bigger args are copied onto the stack, an eightbyte at a time
+    Memory(Vec<Eightbyte>),
+    /// Bigger results are written where the caller asks, see `result_mode`
+    Indirect,
+    /// Unit and Never have nothing to pass, they become a void return
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Eightbyte {
+    offset: u32,
+    /// `F64` when only floats live here, so it goes in an SSE register
+    ty: Type,
+}
+
+pub fn pass_mode(ty: &semantic::Ty, context: &BackendContext) -> PassMode {
+    let layout = ty_layout(ty, context);
+
+    if let semantic::Ty::Unit | semantic::Ty::Never = ty {
+        return PassMode::Ignore;
+    }
+
+    if !ty.is_aggregate() {
+        return PassMode::Direct(ty_to_type(ty, context));
+    }
+
+    if layout.size > 16 {
+        return PassMode::Memory(
+            (0..layout.size.div_ceil(8))
+                .map(|i| Eightbyte {
+                    offset: i * 8,
+                    ty: types::I64,
+                })
+                .collect(),
+        );
+    }
+
+    let leaves = leaves(ty, context);
+    PassMode::Split(
+        (0..layout.size.div_ceil(8))
+            .map(|i| {
+                let offset = i * 8;
+                let only_floats = in_eightbyte(&leaves, offset).all(|(_, ty)| ty.is_float());
+                Eightbyte {
+                    offset,
+                    ty: if only_floats { types::F64 } else { types::I64 },
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Results never go on the stack, anything that would is written to memory the caller gives instead
+pub fn result_mode(ty: &semantic::Ty, context: &BackendContext) -> PassMode {
+    match pass_mode(ty, context) {
+        PassMode::Memory(_) => PassMode::Indirect,
+        mode => mode,
+    }
+}
+
+/// Argument registers left while the params of a signature are placed, in the order C places them
+struct ArgRegisters {
+    gprs: usize,
+    sses: usize,
+    /// Where the next arg that goes on the stack starts
+    stack: i32,
+}
+
+impl ArgRegisters {
+    fn new() -> Self {
+        ArgRegisters {
+            gprs: 6,
+            sses: 8,
+            stack: 0,
+        }
+    }
+
+    /// Take registers for all of these or none of them, a struct is never split between
+    /// registers and the stack. Those that get registers are left for Cranelift to assign,
+    /// it picks the same ones since it counts the same way
+    fn place(&mut self, types: &[Type], params: &mut Vec<AbiParam>) {
+        let sses = types.iter().filter(|ty| ty.is_float()).count();
+        let gprs = types.len() - sses;
+
+        if gprs <= self.gprs && sses <= self.sses {
+            self.gprs -= gprs;
+            self.sses -= sses;
+            params.extend(types.iter().map(|ty| AbiParam::new(*ty)));
+        } else {
+            self.place_on_stack(types, params);
+        }
+    }
+
+    /// Every arg on the stack takes a whole eightbyte
+    fn place_on_stack(&mut self, types: &[Type], params: &mut Vec<AbiParam>) {
+        for ty in types {
+            let mut param = AbiParam::new(*ty);
+            param.location = codegen::ir::ArgumentLoc::Stack(self.stack);
+            params.push(param);
+            self.stack += 8;
+        }
+    }
+}
+
+pub fn make_signature<'a>(
+    params: impl IntoIterator<Item = &'a semantic::Ty>,
+    ty: &semantic::Ty,
+    context: &BackendContext,
+) -> Signature {
+    let pointer_type = context.module.target_config().pointer_type();
+    let mut signature = context.module.make_signature();
+    let mut registers = ArgRegisters::new();
+
+    match result_mode(ty, context) {
+        PassMode::Direct(ty) => signature.returns.push(AbiParam::new(ty)),
+        PassMode::Split(eightbytes) => {
+            for eightbyte in eightbytes {
+                signature.returns.push(AbiParam::new(eightbyte.ty));
+            }
+        }
+        // The caller picks where the result goes, and gets the same address back
+        PassMode::Indirect => {
+            registers.place(&[pointer_type], &mut signature.params);
+            signature.returns.push(AbiParam::new(pointer_type));
+        }
+        PassMode::Memory(_) => unreachable!("Results in memory are indirect"),
+        PassMode::Ignore => {}
+    }
+
+    for param in params {
+        match pass_mode(param, context) {
+            PassMode::Direct(ty) => registers.place(&[ty], &mut signature.params),
+            PassMode::Split(eightbytes) => {
+                let types = eightbytes.iter().map(|e| e.ty).collect::<Vec<_>>();
+                registers.place(&types, &mut signature.params);
+            }
+            PassMode::Memory(eightbytes) => {
+                let types = eightbytes.iter().map(|e| e.ty).collect::<Vec<_>>();
+                registers.place_on_stack(&types, &mut signature.params);
+            }
+            PassMode::Indirect => unreachable!("Only results are indirect"),
+            PassMode::Ignore => {}
+        }
+    }
+
+    signature
+}
+
+/// The signature of one call to a C function declared with `...`, which takes
+/// the extra args like any others. On x86_64 it also says how many of them went
+/// in vector registers, which is passed after them
+pub fn make_variadic_signature<'a>(
+    params: impl IntoIterator<Item = &'a semantic::Ty>,
+    ty: &semantic::Ty,
+    context: &BackendContext,
+) -> (Signature, Option<i64>) {
+    let mut signature = make_signature(params, ty, context);
+    let isa = context.module.isa();
+    if isa.triple().architecture != target_lexicon::Architecture::X86_64 {
+        return (signature, None);
+    }
+
+    let vector_count = signature
+        .params
+        .iter()
+        .filter(|p| p.value_type.is_float())
+        .count()
+        .min(8);
+
+    // The callee reads the count from `%al` to know which vector registers to save
+    let rax = isa
+        .register_info()
+        .parse_regunit("rax")
+        .expect("x86_64 has `rax`");
+    signature.params.push(AbiParam::special_reg(
+        types::I32,
+        codegen::ir::ArgumentPurpose::Normal,
+        rax,
+    ));
+
+    (signature, Some(vector_count as i64))
+}
+
+/// Lower the args, make the call with `emit` and gather what it gives back,
+/// a `receiver` is already lowered and goes before the args
+pub fn call(
+    builder: &mut FunctionBuilder,
+    context: &mut BackendContext,
+    ty: &semantic::Ty,
+    receiver: Option<Value>,
+    args: Vec<semantic::Expression>,
+    emit: impl FnOnce(&mut FunctionBuilder, &[Value]) -> codegen::ir::Inst,
+) -> Value {
+    // `self` is only ever a pointer, whatever type is behind it
+    let params = receiver
+        .map(|_| semantic::Ty::USize)
+        .into_iter()
+        .chain(args.iter().map(|a| a.ty.clone()))
+        .collect::<Vec<_>>();
+    let signature = make_signature(&params, ty, context);
+
+    // Results too big for registers go in memory we hand over first
+    let mut call_args = Vec::with_capacity(args.len());
+    let struct_return = match result_mode(ty, context) {
+        PassMode::Indirect => {
+            let address = create_aggregate(builder, context, ty);
+            call_args.push(address);
+            Some(address)
+        }
+        _ => None,
+    };
+    call_args.extend(receiver);
+
+    for arg in args {
+        let ty = arg.ty.clone();
+        let value = arg.visit_semantic(builder, context);
+        lower_arg(builder, context, &ty, value, &mut call_args);
+    }
+
+    // Cranelift checks stack args are in their slots before it would move them there itself
+    for (value, param) in call_args.iter_mut().zip(&signature.params) {
+        if let codegen::ir::ArgumentLoc::Stack(offset) = param.location {
+            let slot = builder
+                .func
+                .stack_slots
+                .get_outgoing_arg(param.value_type, offset);
+            *value = builder.ins().spill(*value);
+            builder.func.locations[*value] = codegen::ir::ValueLoc::Stack(slot);
+        }
+    }
+
+    let call = emit(builder, &call_args);
+    lift_results(builder, context, ty, call, struct_return)
+}
+
+/// Turn a value into the arguments a call expects
+pub fn lower_arg(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    value: Value,
+    args: &mut Vec<Value>,
+) {
+    match pass_mode(ty, context) {
+        PassMode::Direct(_) => args.push(value),
+        // Where each eightbyte goes is up to the signature
+        PassMode::Split(eightbytes) | PassMode::Memory(eightbytes) => {
+            let pieces = pieces(ty, context);
+            for eightbyte in eightbytes {
+                args.push(load_eightbyte(builder, &pieces, value, eightbyte));
+            }
+        }
+        PassMode::Indirect => unreachable!("Only results are indirect"),
+        PassMode::Ignore => {}
+    }
+}
+
+/// Rebuild a parameter from the values the function was given
+pub fn lift_param(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    params: &mut impl Iterator<Item = Value>,
+) -> Value {
+    let mut next = || params.next().expect("Signature should match params");
+    match pass_mode(ty, context) {
+        PassMode::Direct(_) => next(),
+        PassMode::Split(eightbytes) | PassMode::Memory(eightbytes) => {
+            let address = create_aggregate(builder, context, ty);
+            let pieces = pieces(ty, context);
+            for eightbyte in eightbytes {
+                let value = next();
+                store_eightbyte(builder, &pieces, address, eightbyte, value);
+            }
+            address
+        }
+        PassMode::Indirect => unreachable!("Only results are indirect"),
+        PassMode::Ignore => unit_value(builder),
+    }
+}
+
+/// Gather the result of a call, `struct_return` is where an indirect result was asked to go
+pub fn lift_results(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    call: codegen::ir::Inst,
+    struct_return: Option<Value>,
+) -> Value {
+    let results = builder.inst_results(call).to_vec();
+    match result_mode(ty, context) {
+        PassMode::Direct(_) => results[0],
+        PassMode::Split(eightbytes) => {
+            let address = create_aggregate(builder, context, ty);
+            let pieces = pieces(ty, context);
+            for (eightbyte, value) in eightbytes.into_iter().zip(results) {
+                store_eightbyte(builder, &pieces, address, eightbyte, value);
+            }
+            address
+        }
+        PassMode::Indirect => struct_return.expect("Indirect result needs an address"),
+        PassMode::Memory(_) => unreachable!("Results in memory are indirect"),
+        PassMode::Ignore => unit_value(builder),
+    }
+}
+
+pub fn return_value(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    value: Value,
+) {
+    match result_mode(ty, context) {
+        PassMode::Direct(_) => {
+            builder.ins().return_(&[value]);
+        }
+        PassMode::Split(eightbytes) => {
+            let pieces = pieces(ty, context);
+            let values = eightbytes
+                .into_iter()
+                .map(|e| load_eightbyte(builder, &pieces, value, e))
+                .collect::<Vec<_>>();
+            builder.ins().return_(&values);
+        }
+        PassMode::Indirect => {
+            let struct_return = context
+                .struct_return
+                .expect("Indirect result needs an address");
+            copy_aggregate(builder, context, ty, struct_return, value);
+            builder.ins().return_(&[struct_return]);
+        }
+        PassMode::Memory(_) => unreachable!("Results in memory are indirect"),
+        PassMode::Ignore => {
+            builder.ins().return_(&[]);
+        }
+    }
+}
+
+/// Every scalar inside of an aggregate along with its offset, including each variant of a union
+fn leaves(ty: &semantic::Ty, context: &BackendContext) -> Vec<(u32, semantic::Ty)> {
+    fn walk(
+        ty: &semantic::Ty,
+        offset: u32,
+        context: &BackendContext,
+        leaves: &mut Vec<(u32, semantic::Ty)>,
+    ) {
+        match ty {
+            semantic::Ty::Struct { .. } => {
+                for field in context.struct_table[ty].fields.iter() {
+                    walk(&field.ty, offset + field.offset, context, leaves);
+                }
+            }
+            semantic::Ty::Union { .. } => {
+                leaves.push((offset, semantic::Ty::U32));
+                for variant in context.union_table[ty].variants.iter() {
+                    for field in variant.fields.iter() {
+                        walk(&field.ty, offset + field.offset, context, leaves);
+                    }
+                }
+            }
+            semantic::Ty::Array { of, len } => {
+                let size = ty_size(of, context);
+                for i in 0..*len {
+                    walk(of, offset + i * size, context, leaves);
+                }
+            }
+            semantic::Ty::Slice { .. } | semantic::Ty::Dyn { .. } => {
+                let size = ty_size(&semantic::Ty::USize, context);
+                leaves.push((offset, semantic::Ty::USize));
+                leaves.push((offset + size, semantic::Ty::USize));
+            }
+            semantic::Ty::Unit | semantic::Ty::Never => {}
+            ty => leaves.push((offset, ty.clone())),
+        }
+    }
+
+    let mut leaves = Vec::new();
+    walk(ty, 0, context, &mut leaves);
+    leaves
+}
+
+/// Loads that cover an aggregate without overlapping, unions are copied as plain bytes
+fn pieces(ty: &semantic::Ty, context: &BackendContext) -> Vec<(u32, Type)> {
+    fn walk(
+        ty: &semantic::Ty,
+        offset: u32,
+        context: &BackendContext,
+        pieces: &mut Vec<(u32, Type)>,
+    ) {
+        match ty {
+            semantic::Ty::Struct { .. } => {
+                for field in context.struct_table[ty].fields.iter() {
+                    walk(&field.ty, offset + field.offset, context, pieces);
+                }
+            }
+            semantic::Ty::Union { .. } => {
+                let end = offset + context.union_table[ty].layout.size;
+                let mut start = offset;
+
+                // Biggest aligned chunk that still fits, so an eightbyte is never crossed
+                while start < end {
+                    let size = [8, 4, 2, 1]
+                        .iter()
+                        .copied()
+                        .find(|size| start.is_multiple_of(*size) && start + size <= end)
+                        .expect("A single byte always fits");
+                    pieces.push((start, Type::int(size as u16 * 8).expect("Valid int size")));
+                    start += size;
+                }
+            }
+            semantic::Ty::Array { of, len } => {
+                let size = ty_size(of, context);
+                for i in 0..*len {
+                    walk(of, offset + i * size, context, pieces);
+                }
+            }
+            semantic::Ty::Slice { .. } | semantic::Ty::Dyn { .. } => {
+                let size = ty_size(&semantic::Ty::USize, context);
+                let pointer_type = context.module.target_config().pointer_type();
+                pieces.push((offset, pointer_type));
+                pieces.push((offset + size, pointer_type));
+            }
+            semantic::Ty::Unit | semantic::Ty::Never => {}
+            ty => pieces.push((offset, ty_to_type(ty, context))),
+        }
+    }
+
+    let mut pieces = Vec::new();
+    walk(ty, 0, context, &mut pieces);
+    pieces
+}
+
+fn in_eightbyte<T>(items: &[(u32, T)], offset: u32) -> impl Iterator<Item = &(u32, T)> {
+    items
+        .iter()
+        .filter(move |(start, _)| (offset..offset + 8).contains(start))
+}
+
+/// Pack the fields of one eightbyte into a register, without reading past the struct
+fn load_eightbyte(
+    builder: &mut FunctionBuilder,
+    pieces: &[(u32, Type)],
+    address: Value,
+    eightbyte: Eightbyte,
+) -> Value {
+    let mut bits = builder.ins().iconst(types::I64, 0);
+
+    for &(offset, ty) in in_eightbyte(pieces, eightbyte.offset) {
+        let mut value = builder
+            .ins()
+            .load(ty, MemFlags::new(), address, offset as i32);
+
+        if ty.is_float() {
+            value = builder.ins().bitcast(same_size_int(ty), value);
+        }
+        if ty.bits() < 64 {
+            value = builder.ins().uextend(types::I64, value);
+        }
+
+        let shift = (offset - eightbyte.offset) * 8;
+        let value = builder.ins().ishl_imm(value, shift as i64);
+        bits = builder.ins().bor(bits, value);
+    }
+
+    match eightbyte.ty {
+        types::F64 => builder.ins().bitcast(types::F64, bits),
+        _ => bits,
+    }
+}
+
+/// Unpack a register into the fields of one eightbyte
+fn store_eightbyte(
+    builder: &mut FunctionBuilder,
+    pieces: &[(u32, Type)],
+    address: Value,
+    eightbyte: Eightbyte,
+    value: Value,
+) {
+    let bits = match eightbyte.ty {
+        types::F64 => builder.ins().bitcast(types::I64, value),
+        _ => value,
+    };
+
+    for &(offset, ty) in in_eightbyte(pieces, eightbyte.offset) {
+        let shift = (offset - eightbyte.offset) * 8;
+        let mut value = builder.ins().ushr_imm(bits, shift as i64);
+
+        if ty.bits() < 64 {
+            value = builder.ins().ireduce(same_size_int(ty), value);
+        }
+        if ty.is_float() {
+            value = builder.ins().bitcast(ty, value);
+        }
+
+        builder
+            .ins()
+            .store(MemFlags::new(), value, address, offset as i32);
+    }
+}
+
+fn same_size_int(ty: Type) -> Type {
+    Type::int(ty.bits()).expect("Scalar should be 8 to 64 bits")
+}
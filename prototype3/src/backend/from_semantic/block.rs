@@ -1,7 +1,11 @@
 pub use super::*;
 
 impl semantic::Block {
-    pub fn visit_semantic(self, builder: &mut FunctionBuilder, context: &BackendContext) -> Value {
+    pub fn visit_semantic(
+        self,
+        builder: &mut FunctionBuilder,
+        context: &mut BackendContext,
+    ) -> Value {
         for stmt in self.body {
             stmt.visit_semantic(builder, context);
         }
@@ -11,18 +15,20 @@ impl semantic::Block {
 }
 
 impl semantic::Statement {
-    pub fn visit_semantic(self, builder: &mut FunctionBuilder, context: &BackendContext) {
+    pub fn visit_semantic(self, builder: &mut FunctionBuilder, context: &mut BackendContext) {
         match self {
             Self::LetBinding {
-                value, symbol_id, ..
+                value,
+                symbol_id,
+                ty,
+                ..
             } => {
-                let ty = ty_to_type(value.ty, context);
-
-                let symbol_id = symbol_id.into();
-                let value = value.visit_semantic(builder, context);
-
-                builder.declare_var(symbol_id, ty);
-                builder.def_var(symbol_id, value);
+                let diverges = value.ty == semantic::Ty::Never;
+                let mut value = value.visit_semantic(builder, context);
+                if diverges {
+                    value = dead_value(builder, context, &ty);
+                }
+                define_local(builder, context, symbol_id, &ty, value);
             }
             Self::SideEffect(expr) => {
                 expr.visit_semantic(builder, context);
@@ -7,7 +7,7 @@ impl semantic::Literal {
             Self::I8(num) => builder.ins().iconst(types::I8, num as i64),
             Self::I16(num) => builder.ins().iconst(types::I16, num as i64),
             Self::I32(num) => builder.ins().iconst(types::I32, num as i64),
-            Self::I64(num) => builder.ins().iconst(types::I32, num),
+            Self::I64(num) => builder.ins().iconst(types::I64, num),
             Self::ISize(num) => builder.ins().iconst(pointer_type, num as i64),
             Self::U8(num) => builder.ins().iconst(types::I8, num as i64),
             Self::U16(num) => builder.ins().iconst(types::I16, num as i64),
@@ -16,6 +16,47 @@ impl semantic::Literal {
             Self::USize(num) => builder.ins().iconst(pointer_type, num as i64),
             Self::F32(num) => builder.ins().f32const(num),
             Self::F64(num) => builder.ins().f64const(num),
+            Self::Bool(value) => builder.ins().iconst(types::I8, value as i64),
+            Self::Unit => unit_value(builder),
+        }
+    }
+}
+
+impl semantic::Literal {
+    /// How the value sits in memory, used to fill in statics
+    pub fn to_bytes(self) -> Box<[u8]> {
+        match self {
+            Self::I8(num) => Box::new(num.to_ne_bytes()),
+            Self::I16(num) => Box::new(num.to_ne_bytes()),
+            Self::I32(num) => Box::new(num.to_ne_bytes()),
+            Self::I64(num) => Box::new(num.to_ne_bytes()),
+            Self::ISize(num) => Box::new(num.to_ne_bytes()),
+            Self::U8(num) => Box::new(num.to_ne_bytes()),
+            Self::U16(num) => Box::new(num.to_ne_bytes()),
+            Self::U32(num) => Box::new(num.to_ne_bytes()),
+            Self::U64(num) => Box::new(num.to_ne_bytes()),
+            Self::USize(num) => Box::new(num.to_ne_bytes()),
+            Self::F32(num) => Box::new(num.to_ne_bytes()),
+            Self::F64(num) => Box::new(num.to_ne_bytes()),
+            Self::Bool(value) => Box::new([value as u8]),
+            Self::Unit => Box::new([]),
+        }
+    }
+}
+
+impl semantic::Constant {
+    /// Writes the value into `bytes` starting at `offset`, padding is left as it was
+    pub fn write_bytes(&self, bytes: &mut [u8], offset: usize) {
+        match self {
+            Self::Scalar(literal) => {
+                let literal = literal.to_bytes();
+                bytes[offset..offset + literal.len()].copy_from_slice(&literal);
+            }
+            Self::Aggregate(parts) => {
+                for (part_offset, part) in parts {
+                    part.write_bytes(bytes, offset + *part_offset as usize);
+                }
+            }
         }
     }
 }
@@ -24,17 +65,37 @@ impl semantic::PrefixOperator {
     pub fn visit_semantic(
         self,
         builder: &mut FunctionBuilder,
-        ty: semantic::Ty,
+        ty: &semantic::Ty,
         value: Value,
     ) -> Value {
         use semantic::Ty::*;
         match ty {
             I8 | I16 | I32 | I64 | ISize | U8 | U16 | U32 | U64 | USize => match self {
                 Self::Negate => builder.ins().ineg(value),
+                Self::BitNot => builder.ins().bnot(value),
+                Self::BooleanNot => unreachable!("Checked by type_check"),
             },
             F32 | F64 => match self {
                 Self::Negate => builder.ins().fneg(value),
+                Self::BooleanNot | Self::BitNot => unreachable!("Checked by type_check"),
             },
+            Bool => match self {
+                Self::BooleanNot => builder.ins().bxor_imm(value, 1),
+                Self::Negate | Self::BitNot => unreachable!("Checked by type_check"),
+            },
+            Unit
+            | Never
+            | Pointer { .. }
+            | Func { .. }
+            | Array { .. }
+            | Slice { .. }
+            | Dyn { .. }
+            | Struct { .. }
+            | Union { .. }
+            | Param(_)
+            | Error => {
+                unreachable!("Checked by type_check")
+            }
         }
     }
 }
@@ -43,65 +104,432 @@ impl semantic::InfixOperator {
     pub fn visit_semantic(
         self,
         builder: &mut FunctionBuilder,
-        ty: semantic::Ty,
+        ty: &semantic::Ty,
         left: Value,
         right: Value,
     ) -> Value {
         use semantic::Ty::*;
+
+        // Comparisons give back a `b1`, but Bool is stored as a byte
+        if self.is_comparison() {
+            let result = self.visit_comparison(builder, ty, left, right);
+            return builder.ins().bint(types::I8, result);
+        }
+
         match ty {
-            I8 | I16 | I32 | I64 | ISize => match self {
-                Self::Add => builder.ins().iadd(left, right),
-                Self::Subtract => builder.ins().isub(left, right),
-                Self::Multiply => builder.ins().imul(left, right),
-                Self::Divide => builder.ins().sdiv(left, right),
-
-                Self::Equal => builder.ins().icmp(IntCC::Equal, left, right),
-                Self::NotEqual => builder.ins().icmp(IntCC::NotEqual, left, right),
-                Self::GreaterThan => builder.ins().icmp(IntCC::SignedGreaterThan, left, right),
-                Self::LessThan => builder.ins().icmp(IntCC::SignedLessThan, left, right),
-                Self::GreaterOrEqual => {
-                    builder
-                        .ins()
-                        .icmp(IntCC::SignedGreaterThanOrEqual, left, right)
-                }
-                Self::LessOrEqual => builder
-                    .ins()
-                    .icmp(IntCC::SignedLessThanOrEqual, left, right),
-            },
-            U8 | U16 | U32 | U64 | USize => match self {
-                Self::Add => builder.ins().iadd(left, right),
-                Self::Subtract => builder.ins().isub(left, right),
-                Self::Multiply => builder.ins().imul(left, right),
-                Self::Divide => builder.ins().udiv(left, right),
-
-                Self::Equal => builder.ins().icmp(IntCC::Equal, left, right),
-                Self::NotEqual => builder.ins().icmp(IntCC::NotEqual, left, right),
-                Self::GreaterThan => builder.ins().icmp(IntCC::SignedGreaterThan, left, right),
-                Self::LessThan => builder.ins().icmp(IntCC::SignedLessThan, left, right),
-                Self::GreaterOrEqual => {
-                    builder
-                        .ins()
-                        .icmp(IntCC::SignedGreaterThanOrEqual, left, right)
+            I8 | I16 | I32 | I64 | ISize | U8 | U16 | U32 | U64 | USize | Bool => {
+                let signed = ty.is_signed();
+                match self {
+                    Self::Add => builder.ins().iadd(left, right),
+                    Self::Subtract => builder.ins().isub(left, right),
+                    Self::Multiply => builder.ins().imul(left, right),
+                    Self::Divide if signed => builder.ins().sdiv(left, right),
+                    Self::Divide => builder.ins().udiv(left, right),
+                    Self::Remainder if signed => builder.ins().srem(left, right),
+                    Self::Remainder => builder.ins().urem(left, right),
+
+                    Self::BitAnd => builder.ins().band(left, right),
+                    Self::BitOr => builder.ins().bor(left, right),
+                    Self::BitXor => builder.ins().bxor(left, right),
+                    Self::ShiftLeft => builder.ins().ishl(left, right),
+                    Self::ShiftRight if signed => builder.ins().sshr(left, right),
+                    Self::ShiftRight => builder.ins().ushr(left, right),
+                    _ => unreachable!("Handled elsewhere"),
                 }
-                Self::LessOrEqual => builder
-                    .ins()
-                    .icmp(IntCC::SignedLessThanOrEqual, left, right),
-            },
+            }
             F32 | F64 => match self {
                 Self::Add => builder.ins().fadd(left, right),
                 Self::Subtract => builder.ins().fsub(left, right),
                 Self::Multiply => builder.ins().fmul(left, right),
                 Self::Divide => builder.ins().fdiv(left, right),
-
-                Self::Equal => builder.ins().fcmp(FloatCC::Equal, left, right),
-                Self::NotEqual => builder.ins().fcmp(FloatCC::NotEqual, left, right),
-                Self::GreaterThan => builder.ins().fcmp(FloatCC::GreaterThan, left, right),
-                Self::LessThan => builder.ins().fcmp(FloatCC::LessThan, left, right),
-                Self::GreaterOrEqual => {
-                    builder.ins().fcmp(FloatCC::GreaterThanOrEqual, left, right)
-                }
-                Self::LessOrEqual => builder.ins().fcmp(FloatCC::LessThanOrEqual, left, right),
+                _ => unreachable!("Checked by type_check"),
             },
+            Pointer { .. } => unreachable!("Handled elsewhere"),
+            Unit
+            | Never
+            | Func { .. }
+            | Array { .. }
+            | Slice { .. }
+            | Dyn { .. }
+            | Struct { .. }
+            | Union { .. }
+            | Param(_)
+            | Error => {
+                unreachable!("Checked by type_check")
+            }
+        }
+    }
+
+    fn visit_comparison(
+        self,
+        builder: &mut FunctionBuilder,
+        ty: &semantic::Ty,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        use semantic::Ty::*;
+        match ty {
+            // Every Unit is the same, there's nothing to compare
+            Unit | Never => builder.ins().bconst(types::B1, matches!(self, Self::Equal)),
+            I8
+            | I16
+            | I32
+            | I64
+            | ISize
+            | U8
+            | U16
+            | U32
+            | U64
+            | USize
+            | Bool
+            | Pointer { .. }
+            | Func { .. } => {
+                let condition = match (self, ty.is_signed()) {
+                    (Self::Equal, _) => IntCC::Equal,
+                    (Self::NotEqual, _) => IntCC::NotEqual,
+                    (Self::GreaterThan, true) => IntCC::SignedGreaterThan,
+                    (Self::GreaterThan, false) => IntCC::UnsignedGreaterThan,
+                    (Self::LessThan, true) => IntCC::SignedLessThan,
+                    (Self::LessThan, false) => IntCC::UnsignedLessThan,
+                    (Self::GreaterOrEqual, true) => IntCC::SignedGreaterThanOrEqual,
+                    (Self::GreaterOrEqual, false) => IntCC::UnsignedGreaterThanOrEqual,
+                    (Self::LessOrEqual, true) => IntCC::SignedLessThanOrEqual,
+                    (Self::LessOrEqual, false) => IntCC::UnsignedLessThanOrEqual,
+                    _ => unreachable!("Not a comparison"),
+                };
+                builder.ins().icmp(condition, left, right)
+            }
+            F32 | F64 => {
+                let condition = match self {
+                    Self::Equal => FloatCC::Equal,
+                    Self::NotEqual => FloatCC::NotEqual,
+                    Self::GreaterThan => FloatCC::GreaterThan,
+                    Self::LessThan => FloatCC::LessThan,
+                    Self::GreaterOrEqual => FloatCC::GreaterThanOrEqual,
+                    Self::LessOrEqual => FloatCC::LessThanOrEqual,
+                    _ => unreachable!("Not a comparison"),
+                };
+                builder.ins().fcmp(condition, left, right)
+            }
+            Array { .. }
+            | Slice { .. }
+            | Dyn { .. }
+            | Struct { .. }
+            | Union { .. }
+            | Param(_)
+            | Error => {
+                unreachable!("Checked by type_check")
+            }
+        }
+    }
+}
+
+/// Everything but `&&` and `||`, which skip their right side
+pub fn infix(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    operator: semantic::InfixOperator,
+    ty: &semantic::Ty,
+    left: Value,
+    right: Value,
+) -> Value {
+    match ty.pointee() {
+        // Pointers move by whole elements, not bytes
+        Some(pointee) if operator.is_offset() => {
+            let size = ty_size(pointee, context);
+            let offset = builder.ins().imul_imm(right, size as i64);
+            match operator {
+                semantic::InfixOperator::Add => builder.ins().iadd(left, offset),
+                _ => builder.ins().isub(left, offset),
+            }
+        }
+        _ => operator.visit_semantic(builder, ty, left, right),
+    }
+}
+
+/// Convert between numeric types. Unless `trapping`, integers wrap and floats saturate into integers
+pub fn cast(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    from: &semantic::Ty,
+    to: &semantic::Ty,
+    trapping: bool,
+    value: Value,
+) -> Value {
+    let from_type = ty_to_type(from, context);
+    let to_type = ty_to_type(to, context);
+
+    match (from.is_float(), to.is_float()) {
+        (false, false) if trapping && from.is_integer() && to.is_integer() => {
+            let result = cast(builder, context, from, to, false, value);
+            trap_unless_int_fits(builder, from, to, value, result);
+            result
+        }
+        (false, false) if to_type.bits() > from_type.bits() && from.is_signed() => {
+            builder.ins().sextend(to_type, value)
+        }
+        (false, false) if to_type.bits() > from_type.bits() => {
+            builder.ins().uextend(to_type, value)
+        }
+        (false, false) if to_type.bits() < from_type.bits() => {
+            builder.ins().ireduce(to_type, value)
+        }
+        // Same width, only the signedness changes
+        (false, false) => value,
+
+        (false, true) if from.is_signed() => builder.ins().fcvt_from_sint(to_type, value),
+        (false, true) => builder.ins().fcvt_from_uint(to_type, value),
+
+        (true, false) => float_to_int(builder, to, to_type, trapping, value),
+
+        (true, true) if to_type.bits() > from_type.bits() => builder.ins().fpromote(to_type, value),
+        (true, true) if to_type.bits() < from_type.bits() => builder.ins().fdemote(to_type, value),
+        (true, true) => value,
+    }
+}
+
+/// Trap unless the integer cast to `result` is still the same number
+fn trap_unless_int_fits(
+    builder: &mut FunctionBuilder,
+    from: &semantic::Ty,
+    to: &semantic::Ty,
+    value: Value,
+    result: Value,
+) {
+    let from_type = builder.func.dfg.value_type(value);
+    let to_type = builder.func.dfg.value_type(result);
+    let mut lost = Vec::new();
+
+    // Widened back the way `to` would be, anything cut off comes back different
+    if to_type.bits() < from_type.bits() {
+        let back = if to.is_signed() {
+            builder.ins().sextend(from_type, result)
+        } else {
+            builder.ins().uextend(from_type, result)
+        };
+        lost.push(builder.ins().icmp(IntCC::NotEqual, back, value));
+    }
+
+    // A negative number has no unsigned value, and a big unsigned one reads as negative when signed
+    match (from.is_signed(), to.is_signed()) {
+        (true, false) => lost.push(builder.ins().icmp_imm(IntCC::SignedLessThan, value, 0)),
+        (false, true) => lost.push(builder.ins().icmp_imm(IntCC::SignedLessThan, result, 0)),
+        _ => {}
+    }
+
+    if let Some(first) = lost.pop() {
+        let lost = lost
+            .into_iter()
+            .fold(first, |lost, next| builder.ins().bor(lost, next));
+        builder.ins().trapnz(lost, TrapCode::IntegerOverflow);
+    }
+}
+
+fn float_to_int(
+    builder: &mut FunctionBuilder,
+    to: &semantic::Ty,
+    to_type: Type,
+    trapping: bool,
+    value: Value,
+) -> Value {
+    let signed = to.is_signed();
+
+    // Cranelift only converts to 32 and 64 bit integers, anything smaller is narrowed after
+    if to_type.bits() < 32 {
+        let wide = float_to_int(builder, to, types::I32, trapping, value);
+        let bits = to_type.bits();
+        let (min, max) = if signed {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        } else {
+            (0, (1 << bits) - 1)
+        };
+        let (below, above) = if signed {
+            (IntCC::SignedLessThan, IntCC::SignedGreaterThan)
+        } else {
+            (IntCC::UnsignedLessThan, IntCC::UnsignedGreaterThan)
+        };
+        let too_small = builder.ins().icmp_imm(below, wide, min);
+        let too_big = builder.ins().icmp_imm(above, wide, max);
+
+        let fitted = if trapping {
+            let out_of_range = builder.ins().bor(too_small, too_big);
+            builder
+                .ins()
+                .trapnz(out_of_range, TrapCode::IntegerOverflow);
+            wide
+        } else {
+            let min = builder.ins().iconst(types::I32, min);
+            let max = builder.ins().iconst(types::I32, max);
+            let wide = builder.ins().select(too_small, min, wide);
+            builder.ins().select(too_big, max, wide)
+        };
+        return builder.ins().ireduce(to_type, fitted);
+    }
+
+    match (signed, trapping) {
+        (true, false) => builder.ins().fcvt_to_sint_sat(to_type, value),
+        (false, false) => builder.ins().fcvt_to_uint_sat(to_type, value),
+        (true, true) => builder.ins().fcvt_to_sint(to_type, value),
+        (false, true) => builder.ins().fcvt_to_uint(to_type, value),
+    }
+}
+
+/// Where the elements of an array or slice start and how many there are
+fn elements(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    value: Value,
+) -> (Value, Value) {
+    let pointer_type = context.module.target_config().pointer_type();
+    match ty {
+        semantic::Ty::Array { len, .. } => (value, builder.ins().iconst(pointer_type, *len as i64)),
+        semantic::Ty::Slice { .. } => {
+            let len_offset = semantic::Layout::slice().size / 2;
+            let start = builder.ins().load(pointer_type, MemFlags::new(), value, 0);
+            let len = builder
+                .ins()
+                .load(pointer_type, MemFlags::new(), value, len_offset as i32);
+            (start, len)
         }
+        _ => unreachable!("Checked by type_check"),
+    }
+}
+
+fn element_size(ty: &semantic::Ty, context: &BackendContext) -> u32 {
+    match ty {
+        semantic::Ty::Array { of, .. } | semantic::Ty::Slice { of, .. } => ty_size(of, context),
+        _ => unreachable!("Checked by type_check"),
     }
 }
+
+/// Widen an index to a `USize`, a negative one wraps around to something too big
+fn visit_index(
+    builder: &mut FunctionBuilder,
+    context: &mut BackendContext,
+    index: semantic::Expression,
+) -> Value {
+    let ty = index.ty.clone();
+    let index = index.visit_semantic(builder, context);
+    cast(builder, context, &ty, &semantic::Ty::USize, false, index)
+}
+
+/// Trap unless `low` is below `high`, or equal to it when `inclusive`
+fn check_bounds(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    low: Value,
+    high: Value,
+    inclusive: bool,
+) {
+    if context.unchecked_indexing {
+        return;
+    }
+
+    let condition = match inclusive {
+        true => IntCC::UnsignedLessThanOrEqual,
+        false => IntCC::UnsignedLessThan,
+    };
+    let in_bounds = builder.ins().icmp(condition, low, high);
+    builder.ins().trapz(in_bounds, INDEX_OUT_OF_BOUNDS);
+}
+
+/// Address of one element of an array or slice, after checking it's there
+pub fn element_address(
+    builder: &mut FunctionBuilder,
+    context: &mut BackendContext,
+    value: semantic::Expression,
+    index: semantic::Expression,
+) -> Value {
+    let ty = value.ty.clone();
+    let value = value.visit_semantic(builder, context);
+    let index = visit_index(builder, context, index);
+
+    let (start, len) = elements(builder, context, &ty, value);
+    check_bounds(builder, context, index, len, false);
+
+    let offset = builder
+        .ins()
+        .imul_imm(index, element_size(&ty, context) as i64);
+    builder.ins().iadd(start, offset)
+}
+
+/// A slice of `start..end`, checking that `start <= end <= len` first
+pub fn slice(
+    builder: &mut FunctionBuilder,
+    context: &mut BackendContext,
+    ty: &semantic::Ty,
+    value: semantic::Expression,
+    start: Option<Box<semantic::Expression>>,
+    end: Option<Box<semantic::Expression>>,
+) -> Value {
+    let pointer_type = context.module.target_config().pointer_type();
+    let from = value.ty.clone();
+    let value = value.visit_semantic(builder, context);
+    let (data, len) = elements(builder, context, &from, value);
+
+    let start = match start {
+        Some(start) => visit_index(builder, context, *start),
+        None => builder.ins().iconst(pointer_type, 0),
+    };
+    let end = match end {
+        Some(end) => visit_index(builder, context, *end),
+        None => len,
+    };
+
+    check_bounds(builder, context, start, end, true);
+    check_bounds(builder, context, end, len, true);
+
+    let offset = builder
+        .ins()
+        .imul_imm(start, element_size(&from, context) as i64);
+    let data = builder.ins().iadd(data, offset);
+    let len = builder.ins().isub(end, start);
+
+    let address = create_aggregate(builder, context, ty);
+    let len_offset = semantic::Layout::slice().size / 2;
+    builder.ins().store(MemFlags::new(), data, address, 0);
+    builder
+        .ins()
+        .store(MemFlags::new(), len, address, len_offset as i32);
+    address
+}
+
+/// Copy one value into every element of an array, looping so a big array stays small in code
+pub fn fill(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    address: Value,
+    value: Value,
+) {
+    let pointer_type = context.module.target_config().pointer_type();
+    let size = ty_size(ty, context);
+    let (of, step) = match ty {
+        semantic::Ty::Array { of, .. } => (&**of, ty_size(of, context)),
+        _ => unreachable!("Only arrays are filled"),
+    };
+
+    let header = builder.create_block();
+    let body = builder.create_block();
+    let exit = builder.create_block();
+    builder.append_block_param(header, pointer_type);
+
+    let zero = builder.ins().iconst(pointer_type, 0);
+    builder.ins().jump(header, &[zero]);
+
+    // Counting in bytes saves a multiply each time around
+    builder.switch_to_block(header);
+    let offset = builder.block_params(header)[0];
+    let done = builder.ins().icmp_imm(IntCC::Equal, offset, size as i64);
+    builder.ins().brnz(done, exit, &[]);
+    builder.ins().jump(body, &[]);
+
+    builder.switch_to_block(body);
+    let element = builder.ins().iadd(address, offset);
+    store_at(builder, context, of, element, 0, value);
+    let next = builder.ins().iadd_imm(offset, step as i64);
+    builder.ins().jump(header, &[next]);
+
+    builder.switch_to_block(exit);
+}
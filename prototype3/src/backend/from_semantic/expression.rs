@@ -1,21 +1,26 @@
 pub use super::*;
 
 impl semantic::Expression {
-    pub fn visit_semantic(self, builder: &mut FunctionBuilder, context: &BackendContext) -> Value {
+    pub fn visit_semantic(
+        self,
+        builder: &mut FunctionBuilder,
+        context: &mut BackendContext,
+    ) -> Value {
         use semantic::ExpressionKind::*;
         match self.kind {
             Literal(literal) => literal.visit_semantic(builder, context),
 
-            Lookup { symbol_id, .. } => builder.use_var(symbol_id.into()),
+            Lookup { symbol_id, .. } => use_local(builder, context, symbol_id, &self.ty),
 
             Block(block) => block.visit_semantic(builder, context),
 
             Assignment {
                 symbol_id, value, ..
             } => {
+                let ty = value.ty.clone();
                 let value = value.visit_semantic(builder, context);
-                builder.def_var(symbol_id.into(), value);
-                builder.ins().iconst(types::I32, 0)
+                assign_local(builder, context, symbol_id, &ty, value);
+                unit_value(builder)
             }
 
             FuncCall {
@@ -26,23 +31,148 @@ impl semantic::Expression {
                     .get(&symbol_id)
                     .expect("Func should exist");
 
-                let call = context
-                    .module
-                    .declare_func_in_func(*call, &mut builder.func);
+                let call = context.module.declare_func_in_func(*call, builder.func);
+                abi::call(builder, context, &self.ty, None, args, |builder, args| {
+                    builder.ins().call(call, args)
+                })
+            }
+
+            // A variadic function is declared with just its params, each call gets a signature
+            // with the args it passes, so it goes through the function's address
+            CallVariadic { args, symbol_id } => {
+                let params = args.iter().map(|a| a.ty.clone()).collect::<Vec<_>>();
+                let (signature, vector_count) =
+                    abi::make_variadic_signature(&params, &self.ty, context);
+                let signature = builder.import_signature(signature);
+
+                let func = context
+                    .func_table
+                    .get(&symbol_id)
+                    .expect("Func should exist");
+                let func = context.module.declare_func_in_func(*func, builder.func);
+                let pointer_type = context.module.target_config().pointer_type();
+                let callee = builder.ins().func_addr(pointer_type, func);
+                let vector_count =
+                    vector_count.map(|count| builder.ins().iconst(types::I32, count));
+
+                abi::call(builder, context, &self.ty, None, args, |builder, args| {
+                    let args = args.iter().copied().chain(vector_count).collect::<Vec<_>>();
+                    builder.ins().call_indirect(signature, callee, &args)
+                })
+            }
+
+            CallIndirect { callee, args } => {
+                let signature = match &callee.ty {
+                    semantic::Ty::Func { params, ret } => make_signature(params, ret, context),
+                    _ => unreachable!("Checked by type_check"),
+                };
+                let signature = builder.import_signature(signature);
+                let callee = callee.visit_semantic(builder, context);
+
+                abi::call(builder, context, &self.ty, None, args, |builder, args| {
+                    builder.ins().call_indirect(signature, callee, args)
+                })
+            }
+
+            MakeDyn { pointer, methods } => {
+                let pointer = pointer.visit_semantic(builder, context);
+                let vtable = intern_vtable(context, methods);
+                let global = context.module.declare_data_in_func(vtable, builder.func);
+                let pointer_type = context.module.target_config().pointer_type();
+                let vtable = builder.ins().symbol_value(pointer_type, global);
+
+                let address = create_aggregate(builder, context, &self.ty);
+                let vtable_offset = semantic::Layout::slice().size / 2;
+                builder.ins().store(MemFlags::new(), pointer, address, 0);
+                builder
+                    .ins()
+                    .store(MemFlags::new(), vtable, address, vtable_offset as i32);
+                address
+            }
+
+            CallDyn {
+                object,
+                index,
+                args,
+            } => {
+                let pointer_type = context.module.target_config().pointer_type();
 
-                let args = args
+                // `self` is only ever a pointer, whatever type is behind it
+                let params = Some(semantic::Ty::USize)
                     .into_iter()
-                    .map(|a| a.visit_semantic(builder, context))
+                    .chain(args.iter().map(|a| a.ty.clone()))
                     .collect::<Vec<_>>();
+                let signature = make_signature(&params, &self.ty, context);
+                let signature = builder.import_signature(signature);
 
-                let call = builder.ins().call(call, &args);
-                builder.inst_results(call)[0]
+                let object = object.visit_semantic(builder, context);
+                let vtable_offset = semantic::Layout::slice().size / 2;
+                let pointer = builder.ins().load(pointer_type, MemFlags::new(), object, 0);
+                let vtable =
+                    builder
+                        .ins()
+                        .load(pointer_type, MemFlags::new(), object, vtable_offset as i32);
+                let callee = builder.ins().load(
+                    pointer_type,
+                    MemFlags::new(),
+                    vtable,
+                    (index * pointer_type.bytes()) as i32,
+                );
+
+                abi::call(
+                    builder,
+                    context,
+                    &self.ty,
+                    Some(pointer),
+                    args,
+                    |builder, args| builder.ins().call_indirect(signature, callee, args),
+                )
+            }
+
+            FuncAddress { symbol_id } => {
+                let func = context
+                    .func_table
+                    .get(&symbol_id)
+                    .expect("Func should exist");
+
+                let func = context.module.declare_func_in_func(*func, builder.func);
+                let pointer_type = context.module.target_config().pointer_type();
+                builder.ins().func_addr(pointer_type, func)
             }
 
             PrefixCall { operator, value } => {
-                let ty = value.ty;
+                let ty = value.ty.clone();
                 let value = value.visit_semantic(builder, context);
-                operator.visit_semantic(builder, ty, value)
+                operator.visit_semantic(builder, &ty, value)
+            }
+
+            InfixCall {
+                left,
+                operator,
+                right,
+            } if operator.is_short_circuit() => {
+                let right_block = builder.create_block();
+                let merge_block = builder.create_block();
+                builder.append_block_param(merge_block, types::I8);
+
+                // Skip the right side when the left already decides the result
+                let left = left.visit_semantic(builder, context);
+                match operator {
+                    semantic::InfixOperator::BooleanAnd => {
+                        builder.ins().brz(left, merge_block, &[left])
+                    }
+                    _ => builder.ins().brnz(left, merge_block, &[left]),
+                };
+                builder.ins().jump(right_block, &[]);
+
+                builder.switch_to_block(right_block);
+                builder.seal_block(right_block);
+                let right = right.visit_semantic(builder, context);
+                builder.ins().jump(merge_block, &[right]);
+
+                builder.switch_to_block(merge_block);
+                builder.seal_block(merge_block);
+                builder.block_params(merge_block)[0]
             }
 
             InfixCall {
@@ -50,10 +180,10 @@ impl semantic::Expression {
                 operator,
                 right,
             } => {
-                let ty = left.ty;
+                let ty = left.ty.clone();
                 let left = left.visit_semantic(builder, context);
                 let right = right.visit_semantic(builder, context);
-                operator.visit_semantic(builder, ty, left, right)
+                expr_misc::infix(builder, context, operator, &ty, left, right)
             }
 
             IfElse {
@@ -67,7 +197,7 @@ impl semantic::Expression {
                 let merge_block = builder.create_block();
 
                 // Merge takes the result from either block
-                builder.append_block_param(merge_block, ty_to_type(self.ty, context));
+                builder.append_block_param(merge_block, ty_to_type(&self.ty, context));
 
                 // Jump if predicate is zero, otherwise fall through
                 let predicate = predicate.visit_semantic(builder, context);
@@ -77,20 +207,363 @@ impl semantic::Expression {
                 // Setup when_true block
                 builder.switch_to_block(true_block);
                 builder.seal_block(true_block);
+                let ty = when_true.ty.clone();
                 let when_true = when_true.visit_semantic(builder, context);
-                builder.ins().jump(merge_block, &[when_true]);
+                jump_to_merge(builder, merge_block, &ty, when_true);
 
                 // Same as above but for when_false
                 builder.switch_to_block(else_block);
                 builder.seal_block(else_block);
+                let ty = when_false.ty.clone();
                 let when_false = when_false.visit_semantic(builder, context);
-                builder.ins().jump(merge_block, &[when_false]);
+                jump_to_merge(builder, merge_block, &ty, when_false);
 
                 // Finish the merge and return result
                 builder.switch_to_block(merge_block);
                 builder.seal_block(merge_block);
                 builder.block_params(merge_block)[0]
             }
+
+            Loop { body, symbol_id } => {
+                let header = builder.create_block();
+                let exit = builder.create_block();
+
+                // Exit takes the result from any of the breaks
+                builder.append_block_param(exit, ty_to_type(&self.ty, context));
+                context
+                    .loop_table
+                    .insert(symbol_id, LoopBlocks { header, exit });
+
+                builder.ins().jump(header, &[]);
+
+                // Header can only be sealed once the back edge exists
+                builder.switch_to_block(header);
+                body.visit_semantic(builder, context);
+                builder.ins().jump(header, &[]);
+                builder.seal_block(header);
+
+                builder.switch_to_block(exit);
+                builder.seal_block(exit);
+                builder.block_params(exit)[0]
+            }
+
+            While {
+                predicate,
+                body,
+                symbol_id,
+            } => {
+                let header = builder.create_block();
+                let body_block = builder.create_block();
+                let exit = builder.create_block();
+
+                builder.append_block_param(exit, ty_to_type(&self.ty, context));
+                context
+                    .loop_table
+                    .insert(symbol_id, LoopBlocks { header, exit });
+
+                builder.ins().jump(header, &[]);
+
+                // Leave with `()` once the predicate is false, otherwise run the body
+                builder.switch_to_block(header);
+                let predicate = predicate.visit_semantic(builder, context);
+                let unit = unit_value(builder);
+                builder.ins().brz(predicate, exit, &[unit]);
+                builder.ins().jump(body_block, &[]);
+
+                builder.switch_to_block(body_block);
+                builder.seal_block(body_block);
+                body.visit_semantic(builder, context);
+                builder.ins().jump(header, &[]);
+                builder.seal_block(header);
+
+                builder.switch_to_block(exit);
+                builder.seal_block(exit);
+                builder.block_params(exit)[0]
+            }
+
+            Break { value, symbol_id } => {
+                let LoopBlocks { exit, .. } = context.loop_table[&symbol_id];
+                let value = value.visit_semantic(builder, context);
+                builder.ins().jump(exit, &[value]);
+                switch_to_unreachable(builder)
+            }
+
+            Continue { symbol_id } => {
+                let LoopBlocks { header, .. } = context.loop_table[&symbol_id];
+                builder.ins().jump(header, &[]);
+                switch_to_unreachable(builder)
+            }
+
+            Return { value } => {
+                let ty = value.ty.clone();
+                let value = value.visit_semantic(builder, context);
+                match ty {
+                    semantic::Ty::Never => unreachable_end(builder),
+                    _ => abi::return_value(builder, context, &ty, value),
+                }
+                switch_to_unreachable(builder)
+            }
+
+            AddressOf { symbol_id, .. } => {
+                let slot = context.slot_table[&symbol_id];
+                let pointer_type = context.module.target_config().pointer_type();
+                builder.ins().stack_addr(pointer_type, slot, 0)
+            }
+
+            StaticAddress { symbol_id, .. } => {
+                let data = context.data_table[&symbol_id];
+                let global = context.module.declare_data_in_func(data, builder.func);
+                let pointer_type = context.module.target_config().pointer_type();
+                builder.ins().symbol_value(pointer_type, global)
+            }
+
+            StringLiteral {
+                bytes,
+                nul_terminated,
+            } => {
+                let len = bytes.len() as i64;
+                let data = intern_string(context, bytes);
+                let global = context.module.declare_data_in_func(data, builder.func);
+                let pointer_type = context.module.target_config().pointer_type();
+                let pointer = builder.ins().symbol_value(pointer_type, global);
+
+                if nul_terminated {
+                    return pointer;
+                }
+
+                // A `Str` is a struct of `ptr` then `len`
+                let address = create_aggregate(builder, context, &self.ty);
+                let fields = &context.struct_table[&self.ty].fields;
+                let len = builder.ins().iconst(pointer_type, len);
+                store_at(
+                    builder,
+                    context,
+                    &fields[0].ty,
+                    address,
+                    fields[0].offset,
+                    pointer,
+                );
+                store_at(
+                    builder,
+                    context,
+                    &fields[1].ty,
+                    address,
+                    fields[1].offset,
+                    len,
+                );
+                address
+            }
+
+            Deref(pointer) => {
+                let pointer = pointer.visit_semantic(builder, context);
+
+                // Aggregates are already used through their address
+                if self.ty.is_aggregate() {
+                    return pointer;
+                }
+
+                load_at(builder, context, &self.ty, pointer, 0)
+            }
+
+            Cast { value, trapping } => {
+                let from = value.ty.clone();
+                let value = value.visit_semantic(builder, context);
+                expr_misc::cast(builder, context, &from, &self.ty, trapping, value)
+            }
+
+            DerefAssignment { pointer, value } => {
+                let ty = value.ty.clone();
+                let pointer = pointer.visit_semantic(builder, context);
+                let value = value.visit_semantic(builder, context);
+                store_at(builder, context, &ty, pointer, 0, value);
+                unit_value(builder)
+            }
+
+            CompoundAssignment {
+                pointer,
+                operator,
+                value,
+            } => {
+                let ty = pointer.ty.pointee().expect("Checked by type_check").clone();
+                let pointer = pointer.visit_semantic(builder, context);
+                let left = load_at(builder, context, &ty, pointer, 0);
+                let right = value.visit_semantic(builder, context);
+                let value = expr_misc::infix(builder, context, operator, &ty, left, right);
+                store_at(builder, context, &ty, pointer, 0, value);
+                unit_value(builder)
+            }
+
+            StructLiteral { fields, .. } => {
+                let address = create_aggregate(builder, context, &self.ty);
+
+                for field in fields {
+                    let ty = field.value.ty.clone();
+                    let value = field.value.visit_semantic(builder, context);
+                    store_at(builder, context, &ty, address, field.offset, value);
+                }
+
+                address
+            }
+
+            ArrayLiteral { values } => {
+                let address = create_aggregate(builder, context, &self.ty);
+                let mut offset = 0;
+
+                for value in values {
+                    let ty = value.ty.clone();
+                    let value = value.visit_semantic(builder, context);
+                    store_at(builder, context, &ty, address, offset, value);
+                    offset += ty_size(&ty, context);
+                }
+
+                address
+            }
+
+            ArrayRepeat { value } => {
+                let address = create_aggregate(builder, context, &self.ty);
+                let value = value.visit_semantic(builder, context);
+                expr_misc::fill(builder, context, &self.ty, address, value);
+                address
+            }
+
+            Index { value, index } => {
+                let address = expr_misc::element_address(builder, context, *value, *index);
+                load_at(builder, context, &self.ty, address, 0)
+            }
+
+            // The same as reading the part, stopping at its address
+            PartAddress(part) => match part.kind {
+                FieldAccess { value, offset, .. } => {
+                    let address = value.visit_semantic(builder, context);
+                    builder.ins().iadd_imm(address, offset as i64)
+                }
+                Index { value, index } => {
+                    expr_misc::element_address(builder, context, *value, *index)
+                }
+                _ => unreachable!("Only fields and elements are parts"),
+            },
+
+            Slice { value, start, end } => {
+                expr_misc::slice(builder, context, &self.ty, *value, start, end)
+            }
+
+            FieldAccess { value, offset, .. } => {
+                let address = value.visit_semantic(builder, context);
+                load_at(builder, context, &self.ty, address, offset)
+            }
+
+            VariantLiteral { tag, fields } => {
+                let address = create_aggregate(builder, context, &self.ty);
+
+                let tag = builder.ins().iconst(types::I32, tag as i64);
+                builder.ins().store(MemFlags::new(), tag, address, 0);
+
+                for field in fields {
+                    let ty = field.value.ty.clone();
+                    let value = field.value.visit_semantic(builder, context);
+                    store_at(builder, context, &ty, address, field.offset, value);
+                }
+
+                address
+            }
+
+            Match {
+                value,
+                arms,
+                variants,
+            } => {
+                let is_union = matches!(value.ty, semantic::Ty::Union { .. });
+                let address = value.visit_semantic(builder, context);
+
+                let merge_block = builder.create_block();
+                builder.append_block_param(merge_block, ty_to_type(&self.ty, context));
+
+                let arm_blocks = arms
+                    .iter()
+                    .map(|_| builder.create_block())
+                    .collect::<Vec<_>>();
+
+                // Tags no arm names go to the wildcard, type_check made sure there is one
+                let wildcard = arms
+                    .iter()
+                    .position(|arm| matches!(arm.pattern, semantic::Pattern::Wildcard))
+                    .map(|i| arm_blocks[i]);
+
+                let trap_block = builder.create_block();
+                let default = wildcard.unwrap_or(trap_block);
+
+                if is_union {
+                    let mut targets = vec![default; variants as usize];
+                    for (arm, &block) in arms.iter().zip(arm_blocks.iter()) {
+                        if let semantic::Pattern::Variant { tag, .. } = arm.pattern {
+                            targets[tag as usize] = block;
+                        }
+                    }
+
+                    let mut jump_table = JumpTableData::with_capacity(targets.len());
+                    for block in targets {
+                        jump_table.push_entry(block);
+                    }
+                    let jump_table = builder.create_jump_table(jump_table);
+
+                    let tag = builder.ins().load(types::I32, MemFlags::new(), address, 0);
+                    builder.ins().br_table(tag, default, jump_table);
+                } else {
+                    builder.ins().jump(default, &[]);
+                }
+
+                // Only reachable through a corrupt tag
+                builder.switch_to_block(trap_block);
+                builder.seal_block(trap_block);
+                builder.ins().trap(TrapCode::UnreachableCodeReached);
+
+                for (arm, block) in arms.into_iter().zip(arm_blocks) {
+                    builder.switch_to_block(block);
+                    builder.seal_block(block);
+
+                    // Pull the payload out into locals before the arm runs
+                    if let semantic::Pattern::Variant { bindings, .. } = arm.pattern {
+                        for binding in bindings {
+                            let value =
+                                load_at(builder, context, &binding.ty, address, binding.offset);
+                            define_local(builder, context, binding.symbol_id, &binding.ty, value);
+                        }
+                    }
+
+                    let ty = arm.value.ty.clone();
+                    let result = arm.value.visit_semantic(builder, context);
+                    jump_to_merge(builder, merge_block, &ty, result);
+                }
+
+                builder.switch_to_block(merge_block);
+                builder.seal_block(merge_block);
+                builder.block_params(merge_block)[0]
+            }
+
+            Error => unreachable!("Files with errors aren't compiled"),
+        }
+    }
+}
+
+/// Branches that jump away have no result, and their placeholder isn't the right type anyway
+fn jump_to_merge(
+    builder: &mut FunctionBuilder,
+    merge_block: Block,
+    ty: &semantic::Ty,
+    value: Value,
+) {
+    match ty {
+        semantic::Ty::Never => unreachable_end(builder),
+        _ => {
+            builder.ins().jump(merge_block, &[value]);
         }
     }
 }
+
+/// Anything after a jump still needs a block to live in, even if it's dead code
+fn switch_to_unreachable(builder: &mut FunctionBuilder) -> Value {
+    let block = builder.create_block();
+    builder.switch_to_block(block);
+    builder.seal_block(block);
+    unit_value(builder)
+}
@@ -1,44 +1,314 @@
+mod abi;
 mod block;
 mod expr_misc;
 mod expression;
 
 pub use {
-    super::{super::semantic::type_check as semantic, BackendContext},
-    cranelift::prelude::*,
+    super::{super::semantic::type_check as semantic, BackendContext, LoopBlocks},
+    abi::make_signature,
+    cranelift::{codegen, prelude::*},
+    cranelift_module::{DataContext, DataId, Linkage},
 };
 
 impl semantic::Function {
-    pub fn visit_semantic(self, builder: &mut FunctionBuilder, context: &BackendContext) {
+    pub fn visit_semantic(self, builder: &mut FunctionBuilder, context: &mut BackendContext) {
         let block = builder.create_block();
 
         builder.append_block_params_for_function_params(block);
         builder.switch_to_block(block);
         builder.seal_block(block);
 
-        for (i, param) in self.params.iter().enumerate() {
-            builder.declare_var(param.symbol_id.into(), ty_to_type(param.ty, context));
-            builder.def_var(param.symbol_id.into(), builder.block_params(block)[i]);
+        let mut params = builder.block_params(block).to_vec().into_iter();
+
+        // Big results get written to memory the caller passes first
+        context.struct_return = match abi::result_mode(&self.ty, context) {
+            abi::PassMode::Indirect => params.next(),
+            _ => None,
+        };
+
+        for param in self.params.iter() {
+            let value = abi::lift_param(builder, context, &param.ty, &mut params);
+            define_local(builder, context, param.symbol_id, &param.ty, value);
         }
 
+        let ty = self.body.ty.clone();
         let result = self.body.visit_semantic(builder, context);
 
-        builder.ins().return_(&[result]);
+        // A body that always returns early has nothing left to return
+        match ty {
+            semantic::Ty::Never => unreachable_end(builder),
+            _ => abi::return_value(builder, context, &self.ty, result),
+        }
 
         builder.seal_all_blocks();
         builder.finalize();
     }
 }
 
-pub fn ty_to_type(ty: semantic::Ty, context: &BackendContext) -> Type {
+pub fn ty_to_type(ty: &semantic::Ty, context: &BackendContext) -> Type {
     use semantic::Ty;
     match ty {
-        Ty::I8 | Ty::U8 => types::I8,
+        // Unit still needs a placeholder to flow through blocks, it never reaches memory
+        Ty::I8 | Ty::U8 | Ty::Bool | Ty::Unit | Ty::Never => types::I8,
         Ty::I16 | Ty::U16 => types::I16,
         Ty::I32 | Ty::U32 => types::I32,
         Ty::I64 | Ty::U64 => types::I64,
-        Ty::ISize | Ty::USize => context.module.target_config().pointer_type(),
+        Ty::ISize | Ty::USize | Ty::Pointer { .. } | Ty::Func { .. } => {
+            context.module.target_config().pointer_type()
+        }
         Ty::F32 => types::F32,
         Ty::F64 => types::F64,
+        // Aggregates are handled through the address of their memory
+        Ty::Array { .. }
+        | Ty::Slice { .. }
+        | Ty::Dyn { .. }
+        | Ty::Struct { .. }
+        | Ty::Union { .. } => context.module.target_config().pointer_type(),
+        Ty::Param(_) => unreachable!("Type parameters are replaced before the backend"),
+        Ty::Error => unreachable!("Files with errors aren't compiled"),
+    }
+}
+
+pub fn ty_layout(ty: &semantic::Ty, context: &BackendContext) -> semantic::Layout {
+    match ty {
+        semantic::Ty::Struct { .. } => context.struct_table[ty].layout,
+        semantic::Ty::Union { .. } => context.union_table[ty].layout,
+        semantic::Ty::Array { of, len } => {
+            let layout = ty_layout(of, context);
+            semantic::Layout {
+                size: layout.size * len,
+                align: layout.align,
+            }
+        }
+        semantic::Ty::Slice { .. } | semantic::Ty::Dyn { .. } => semantic::Layout::slice(),
+        ty => ty
+            .scalar_layout()
+            .expect("Only aggregates lack a scalar layout"),
+    }
+}
+
+/// Size in bytes, used to step pointers by whole elements
+pub fn ty_size(ty: &semantic::Ty, context: &BackendContext) -> u32 {
+    ty_layout(ty, context).size
+}
+
+/// Unit has no data, but every expression still hands back a value
+pub fn unit_value(builder: &mut FunctionBuilder) -> Value {
+    builder.ins().iconst(types::I8, 0)
+}
+
+/// Read a value out of memory, aggregates are used through their address instead
+pub fn load_at(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    address: Value,
+    offset: u32,
+) -> Value {
+    match ty {
+        semantic::Ty::Unit | semantic::Ty::Never => unit_value(builder),
+        ty if ty.is_aggregate() => builder.ins().iadd_imm(address, offset as i64),
+        ty => builder.ins().load(
+            ty_to_type(ty, context),
+            MemFlags::new(),
+            address,
+            offset as i32,
+        ),
+    }
+}
+
+/// Write a value into memory, copying the whole thing for aggregates
+pub fn store_at(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    address: Value,
+    offset: u32,
+    value: Value,
+) {
+    match ty {
+        semantic::Ty::Unit | semantic::Ty::Never => {}
+        ty if ty.is_aggregate() => {
+            let address = builder.ins().iadd_imm(address, offset as i64);
+            copy_aggregate(builder, context, ty, address, value);
+        }
+        _ => {
+            builder
+                .ins()
+                .store(MemFlags::new(), value, address, offset as i32);
+        }
+    }
+}
+
+/// Read only data holding the bytes and a NUL after them, shared by equal literals
+pub fn intern_string(context: &mut BackendContext, bytes: Vec<u8>) -> DataId {
+    if let Some(&id) = context.string_table.get(&bytes) {
+        return id;
+    }
+
+    let name = format!("str.{}", context.string_table.len());
+    let id = context
+        .module
+        .declare_data(&name, Linkage::Local, false, false, None)
+        .expect("String names are unique");
+
+    let mut data = DataContext::new();
+    data.define(bytes.iter().copied().chain(Some(0)).collect());
+    context
+        .module
+        .define_data(id, &data)
+        .expect("String was only declared");
+
+    context.string_table.insert(bytes, id);
+    id
+}
+
+/// Addresses of an impl's methods in the order of their trait, shared by every `dyn` of that impl
+pub fn intern_vtable(context: &mut BackendContext, methods: Vec<semantic::SymbolId>) -> DataId {
+    if let Some(&id) = context.vtable_table.get(&methods) {
+        return id;
+    }
+
+    let name = format!("vtable.{}", context.vtable_table.len());
+    let pointer_bytes = context.module.target_config().pointer_bytes() as u32;
+    let id = context
+        .module
+        .declare_data(
+            &name,
+            Linkage::Local,
+            false,
+            false,
+            Some(pointer_bytes as u8),
+        )
+        .expect("Vtable names are unique");
+
+    let mut data = DataContext::new();
+    // Zero-init data would land in bss, which drops the relocations
+    data.define(vec![0; (methods.len() as u32 * pointer_bytes) as usize].into_boxed_slice());
+    for (i, method) in methods.iter().enumerate() {
+        let func = context.func_table[method];
+        let func = context.module.declare_func_in_data(func, &mut data);
+        data.write_function_addr(i as u32 * pointer_bytes, func);
+    }
+
+    context
+        .module
+        .define_data(id, &data)
+        .expect("Vtable was only declared");
+
+    context.vtable_table.insert(methods, id);
+    id
+}
+
+/// Stands in for a value that never arrives, only ever used by dead code
+pub fn dead_value(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+) -> Value {
+    match ty {
+        ty if ty.is_aggregate() => create_aggregate(builder, context, ty),
+        semantic::Ty::F32 => builder.ins().f32const(0.0),
+        semantic::Ty::F64 => builder.ins().f64const(0.0),
+        ty => builder.ins().iconst(ty_to_type(ty, context), 0),
+    }
+}
+
+/// Raised by a failed bounds check, Cranelift leaves user codes to us
+pub const INDEX_OUT_OF_BOUNDS: TrapCode = TrapCode::User(0);
+
+/// End a block that can't be reached, code after a jump still needs a terminator
+pub fn unreachable_end(builder: &mut FunctionBuilder) {
+    builder.ins().trap(TrapCode::UnreachableCodeReached);
+}
+
+/// Fresh stack memory for an aggregate, giving back its address
+pub fn create_aggregate(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+) -> Value {
+    let slot = builder.create_stack_slot(StackSlotData::new(
+        StackSlotKind::ExplicitSlot,
+        ty_size(ty, context),
+    ));
+    let pointer_type = context.module.target_config().pointer_type();
+    builder.ins().stack_addr(pointer_type, slot, 0)
+}
+
+pub fn copy_aggregate(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    ty: &semantic::Ty,
+    dest: Value,
+    src: Value,
+) {
+    let layout = ty_layout(ty, context);
+    builder.emit_small_memory_copy(
+        context.module.target_config(),
+        dest,
+        src,
+        layout.size as u64,
+        layout.align as u8,
+        layout.align as u8,
+        false,
+    );
+}
+
+/// Bind a new local, spilling it to a stack slot if it ever has its address taken
+pub fn define_local(
+    builder: &mut FunctionBuilder,
+    context: &mut BackendContext,
+    symbol_id: semantic::SymbolId,
+    ty: &semantic::Ty,
+    value: Value,
+) {
+    // Structs and unions always live in memory, and get their own copy of the value
+    if ty.is_aggregate() || context.address_taken.contains(&symbol_id) {
+        let slot = builder.create_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            ty_size(ty, context),
+        ));
+        let pointer_type = context.module.target_config().pointer_type();
+        let address = builder.ins().stack_addr(pointer_type, slot, 0);
+        store_at(builder, context, ty, address, 0, value);
+        context.slot_table.insert(symbol_id, slot);
+    } else {
+        builder.declare_var(symbol_id.into(), ty_to_type(ty, context));
+        builder.def_var(symbol_id.into(), value);
+    }
+}
+
+pub fn use_local(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    symbol_id: semantic::SymbolId,
+    ty: &semantic::Ty,
+) -> Value {
+    let pointer_type = context.module.target_config().pointer_type();
+    match context.slot_table.get(&symbol_id) {
+        Some(&slot) => {
+            let address = builder.ins().stack_addr(pointer_type, slot, 0);
+            load_at(builder, context, ty, address, 0)
+        }
+        None => builder.use_var(symbol_id.into()),
+    }
+}
+
+pub fn assign_local(
+    builder: &mut FunctionBuilder,
+    context: &BackendContext,
+    symbol_id: semantic::SymbolId,
+    ty: &semantic::Ty,
+    value: Value,
+) {
+    let pointer_type = context.module.target_config().pointer_type();
+    match context.slot_table.get(&symbol_id) {
+        Some(&slot) => {
+            let address = builder.ins().stack_addr(pointer_type, slot, 0);
+            store_at(builder, context, ty, address, 0, value);
+        }
+        None => builder.def_var(symbol_id.into(), value),
     }
 }
 
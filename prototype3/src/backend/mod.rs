@@ -1,35 +1,70 @@
 pub mod from_semantic;
 
 use {
-    super::semantic::type_check as semantic,
-    cranelift::{codegen::binemit::NullTrapSink, prelude::*},
-    cranelift_module::{FuncId, Linkage, Module},
+    super::{semantic::type_check as semantic, CompileOptions},
+    cranelift::{
+        codegen::{
+            binemit::{CodeOffset, TrapSink},
+            ir::{SourceLoc, StackSlot},
+        },
+        prelude::*,
+    },
+    cranelift_module::{DataContext, DataId, FuncId, Linkage, Module},
     cranelift_object::{ObjectBackend, ObjectBuilder},
-    from_semantic::ty_to_type,
-    std::collections::HashMap,
+    from_semantic::make_signature,
+    std::collections::{HashMap, HashSet},
 };
 
-pub fn backend_pass(file: semantic::File) -> Result<Vec<u8>, BackendError> {
-    let mut context = BackendContext::new(file.items.len())?;
+/// `name` is the entry module's, it keeps symbols that every object has apart when they're linked together
+pub fn backend_pass(
+    file: semantic::File,
+    name: &str,
+    options: &CompileOptions,
+) -> Result<Vec<u8>, BackendError> {
+    let mut context = BackendContext::new(file.items.len(), file.address_taken)?;
+    context.unchecked_indexing = options.unchecked_indexing;
+
+    // Signatures depend on type layouts, so those have to be known first
+    for item in file.items.iter() {
+        match item {
+            semantic::Item::Struct(item) => {
+                context.struct_table.insert(item.ty(), item.clone());
+            }
+            semantic::Item::Union(item) => {
+                context.union_table.insert(item.ty(), item.clone());
+            }
+            _ => {}
+        }
+    }
 
     let mut ctx = context.module.make_context();
     let mut builder_context = FunctionBuilderContext::new();
+    let mut traps = TrapTable::default();
 
     file.items
         .into_iter()
         .filter_map(|item| match item {
             semantic::Item::Declare(declare) => {
-                for func in declare.functions {
-                    let mut signature = context.module.make_signature();
-                    signature
-                        .returns
-                        .push(AbiParam::new(ty_to_type(func.ty, &context)));
+                for item in declare.statics {
+                    let id = context.module.declare_data(
+                        item.name.as_string(),
+                        Linkage::Import,
+                        item.mutable,
+                        false,
+                        None,
+                    );
+
+                    let id = match id {
+                        Ok(id) => id,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+
+                    context.data_table.insert(item.symbol_id, id);
+                }
 
-                    for arg in func.params.iter() {
-                        signature
-                            .params
-                            .push(AbiParam::new(ty_to_type(arg.ty, &context)));
-                    }
+                for func in declare.functions {
+                    let params = func.params.iter().map(|p| &p.ty);
+                    let signature = make_signature(params, &func.ty, &context);
 
                     let id = context.module.declare_function(
                         func.name.as_string(),
@@ -48,16 +83,8 @@ pub fn backend_pass(file: semantic::File) -> Result<Vec<u8>, BackendError> {
             }
 
             semantic::Item::Function(func) => {
-                let mut signature = context.module.make_signature();
-                signature
-                    .returns
-                    .push(AbiParam::new(ty_to_type(func.ty, &context)));
-
-                for arg in func.params.iter() {
-                    signature
-                        .params
-                        .push(AbiParam::new(ty_to_type(arg.ty, &context)));
-                }
+                let params = func.params.iter().map(|p| &p.ty);
+                let signature = make_signature(params, &func.ty, &context);
 
                 let id = context.module.declare_function(
                     func.name.as_string(),
@@ -73,24 +100,57 @@ pub fn backend_pass(file: semantic::File) -> Result<Vec<u8>, BackendError> {
                 context.func_table.insert(func.symbol_id, id);
                 Some(Ok((id, signature, func)))
             }
+
+            // Immutable statics end up in `.rodata`, the rest in `.data`
+            semantic::Item::Static(item) => {
+                let layout = from_semantic::ty_layout(&item.ty, &context);
+
+                let id = context.module.declare_data(
+                    item.name.as_string(),
+                    item.scope.into(),
+                    item.mutable,
+                    false,
+                    Some(layout.align as u8),
+                );
+
+                let id = match id {
+                    Ok(id) => id,
+                    Err(e) => return Some(Err(e.into())),
+                };
+
+                let mut bytes = vec![0; layout.size as usize];
+                item.value.write_bytes(&mut bytes, 0);
+
+                let mut data = DataContext::new();
+                data.define(bytes.into_boxed_slice());
+
+                if let Err(e) = context.module.define_data(id, &data) {
+                    return Some(Err(e.into()));
+                }
+
+                context.data_table.insert(item.symbol_id, id);
+                None
+            }
+
+            semantic::Item::Struct(_) | semantic::Item::Union(_) => None,
         })
         .collect::<Result<Vec<_>, BackendError>>()?
         .into_iter()
-        .for_each(|(id, signature, func)| {
+        .try_for_each(|(id, signature, func)| {
             ctx.func.signature = signature;
 
             let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_context);
 
-            func.visit_semantic(&mut builder, &context);
+            func.visit_semantic(&mut builder, &mut context);
 
-            context
-                .module
-                .define_function(id, &mut ctx, &mut NullTrapSink {})
-                .unwrap();
+            traps.func = Some(id);
+            context.module.define_function(id, &mut ctx, &mut traps)?;
 
             context.module.clear_context(&mut ctx);
-        });
+            Ok::<_, BackendError>(())
+        })?;
 
+    traps.define(name, &mut context)?;
     context.module.finalize_definitions();
 
     Ok(context.module.finish().emit()?)
@@ -98,11 +158,36 @@ pub fn backend_pass(file: semantic::File) -> Result<Vec<u8>, BackendError> {
 
 pub struct BackendContext {
     func_table: HashMap<semantic::SymbolId, FuncId>,
+    data_table: HashMap<semantic::SymbolId, DataId>,
+    /// Each string literal only goes in the object once, however often it's used
+    string_table: HashMap<Vec<u8>, DataId>,
+    /// Keyed by the methods in them, equal impls share one vtable
+    vtable_table: HashMap<Vec<semantic::SymbolId>, DataId>,
+    loop_table: HashMap<semantic::SymbolId, LoopBlocks>,
+    slot_table: HashMap<semantic::SymbolId, StackSlot>,
+    /// Keyed by the whole type, each instance of a generic type is laid out differently
+    struct_table: HashMap<semantic::Ty, semantic::Struct>,
+    union_table: HashMap<semantic::Ty, semantic::Union>,
+    address_taken: HashSet<semantic::SymbolId>,
+    /// Where the current function writes a result too big for registers
+    struct_return: Option<Value>,
+    /// Leave out bounds checks, an index past the end reads or writes whatever is there
+    unchecked_indexing: bool,
     module: Module<ObjectBackend>,
 }
 
+/// Jump targets for `continue` and `break`
+#[derive(Debug, Clone, Copy)]
+pub struct LoopBlocks {
+    header: Block,
+    exit: Block,
+}
+
 impl BackendContext {
-    fn new(capacity: usize) -> Result<Self, BackendError> {
+    fn new(
+        capacity: usize,
+        address_taken: HashSet<semantic::SymbolId>,
+    ) -> Result<Self, BackendError> {
         let isa =
             isa::lookup(target_lexicon::HOST)?.finish(settings::Flags::new(settings::builder()));
 
@@ -112,14 +197,92 @@ impl BackendContext {
         Ok(Self {
             module: Module::new(builder),
             func_table: HashMap::with_capacity(capacity),
+            data_table: HashMap::new(),
+            string_table: HashMap::new(),
+            vtable_table: HashMap::new(),
+            loop_table: HashMap::new(),
+            slot_table: HashMap::new(),
+            struct_table: HashMap::new(),
+            union_table: HashMap::new(),
+            address_taken,
+            struct_return: None,
+            unchecked_indexing: false,
         })
     }
 }
 
+/// Where every trap in the object is, so a signal handler can tell what went wrong
+#[derive(Default)]
+struct TrapTable {
+    /// The function being defined, trap offsets are from its start
+    func: Option<FuncId>,
+    sites: Vec<(FuncId, CodeOffset, TrapCode)>,
+}
+
+impl TrapSink for TrapTable {
+    fn trap(&mut self, offset: CodeOffset, _: SourceLoc, code: TrapCode) {
+        let func = self.func.expect("Traps only come from a function");
+        self.sites.push((func, offset, code));
+    }
+}
+
+impl TrapTable {
+    /// Exported as `sonance_trap_table_` and the object's name, each entry is the address of a function,
+    /// then the `U32` offset of the trap in it and a `U32` for its kind, ending with a null address
+    fn define(self, name: &str, context: &mut BackendContext) -> Result<(), BackendError> {
+        let pointer_bytes = context.module.target_config().pointer_bytes() as usize;
+        let entry_size = pointer_bytes + 8;
+
+        let mut data = DataContext::new();
+        let mut bytes = vec![0; (self.sites.len() + 1) * entry_size];
+
+        for (i, (func, offset, code)) in self.sites.into_iter().enumerate() {
+            let entry = i * entry_size;
+            let func = context.module.declare_func_in_data(func, &mut data);
+            data.write_function_addr(entry as u32, func);
+
+            let entry = &mut bytes[entry + pointer_bytes..entry + entry_size];
+            entry[..4].copy_from_slice(&offset.to_ne_bytes());
+            entry[4..].copy_from_slice(&trap_kind(code).to_ne_bytes());
+        }
+
+        // Anything that can't be in a C identifier becomes `_`
+        let name = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        let id = context.module.declare_data(
+            &format!("sonance_trap_table_{}", name),
+            Linkage::Export,
+            false,
+            false,
+            Some(pointer_bytes as u8),
+        )?;
+        data.define(bytes.into_boxed_slice());
+        context.module.define_data(id, &data)?;
+
+        Ok(())
+    }
+}
+
+/// Numbers for the kinds of trap, anything else is 0
+fn trap_kind(code: TrapCode) -> u32 {
+    match code {
+        TrapCode::UnreachableCodeReached => 1,
+        from_semantic::INDEX_OUT_OF_BOUNDS => 2,
+        TrapCode::IntegerOverflow => 3,
+        TrapCode::BadConversionToInteger => 4,
+        TrapCode::IntegerDivisionByZero => 5,
+        _ => 0,
+    }
+}
+
 impl From<semantic::Scope> for Linkage {
     fn from(scope: semantic::Scope) -> Self {
         match scope {
-            semantic::Scope::Local => Linkage::Local,
+            // Every module ends up in the same object, so shared items stay local to it
+            semantic::Scope::Local | semantic::Scope::Shared => Linkage::Local,
             semantic::Scope::Public => Linkage::Export,
         }
     }
@@ -132,7 +295,13 @@ pub enum BackendError {
     #[error("Failed to lookup instruction set")]
     Lookup(#[from] cranelift::codegen::isa::LookupError),
     #[error("Error while using cranelift Module")]
-    Module(#[from] cranelift_module::ModuleError),
+    Module(#[from] Box<cranelift_module::ModuleError>),
     #[error("Error while emitting object blob")]
     Object(#[from] cranelift_object::object::write::Error),
 }
+
+impl From<cranelift_module::ModuleError> for BackendError {
+    fn from(error: cranelift_module::ModuleError) -> Self {
+        Box::new(error).into()
+    }
+}
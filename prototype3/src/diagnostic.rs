@@ -0,0 +1,89 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Where a node was written, as byte offsets into one of the files in the `SourceMap`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: usize, start: usize, end: usize) -> Self {
+        Span { file, start, end }
+    }
+}
+
+/// Every file read while compiling, so spans can be shown as the source they point at
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+}
+
+impl SourceMap {
+    pub fn add(&mut self, path: PathBuf, text: String) -> usize {
+        self.files.push(SourceFile { path, text });
+        self.files.len() - 1
+    }
+
+    pub fn text(&self, file: usize) -> &str {
+        &self.files[file].text
+    }
+
+    pub fn path(&self, file: usize) -> &Path {
+        &self.files[file].path
+    }
+
+    /// `level: message`, then `file:line:column` and the line itself with the span underlined,
+    /// a span over many lines is only underlined to the end of its first
+    pub fn render(&self, level: &str, message: &dyn fmt::Display, span: Option<Span>) -> String {
+        let mut out = format!("{}: {}\n", level, message);
+        let span = match span {
+            Some(span) => span,
+            None => return out,
+        };
+
+        let file = &self.files[span.file];
+        let start = span.start.min(file.text.len());
+        let line_start = file.text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = file.text[start..]
+            .find('\n')
+            .map_or(file.text.len(), |i| start + i);
+        let line = file.text[line_start..line_end].trim_end_matches('\r');
+
+        let number = file.text[..line_start].matches('\n').count() + 1;
+        let column = file.text[line_start..start].chars().count() + 1;
+        let underlined = file.text[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        // Tabs are kept under the line so the carets stay lined up with it
+        let indent = file.text[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let gutter = " ".repeat(number.to_string().len());
+
+        out += &format!(
+            "{}--> {}:{}:{}\n",
+            gutter,
+            file.path.display(),
+            number,
+            column
+        );
+        out += &format!("{} |\n", gutter);
+        out += &format!("{} | {}\n", number, line);
+        out += &format!("{} | {}{}\n", gutter, indent, "^".repeat(underlined));
+        out
+    }
+}
@@ -3,24 +3,161 @@ extern crate lalrpop_util;
 
 pub mod ast;
 pub mod backend;
+pub mod diagnostic;
+pub mod module;
 pub mod semantic;
 
-pub fn compile(input: &'_ str) -> Result<Vec<u8>, CompileError> {
-    let ast = ast::ast_pass(input).map_err(CompileError::Parse)?;
-    let semantic = semantic::semantic_pass(ast)?;
-    let binary = backend::backend_pass(semantic)?;
+use {
+    diagnostic::SourceMap,
+    semantic::{Lint, LintLevel},
+    std::{collections::HashMap, path::PathBuf},
+};
 
-    Ok(binary)
+/// Compiles the `entry` file and every module it imports into one object,
+/// imported files are added to `sources` as they're read
+pub fn compile(
+    sources: &mut SourceMap,
+    entry: usize,
+    options: &CompileOptions,
+) -> Result<Compiled, CompileError> {
+    let mut syntax_errors = Vec::new();
+    let ast = match ast::ast_pass(sources.text(entry), entry, &mut syntax_errors) {
+        Some(ast) => ast,
+        None => {
+            return Err(CompileError::Parse {
+                errors: syntax_errors,
+                checked: None,
+            })
+        }
+    };
+
+    // Files with the same name in different directories are kept apart by the module they say they are
+    let name = match &ast.module {
+        Some(module) => module.as_string().to_string(),
+        None => {
+            let stem = sources.path(entry).file_stem().unwrap_or_default();
+            stem.to_string_lossy().into_owned()
+        }
+    };
+
+    // Whatever did parse is still checked, so one run shows every mistake it can
+    let checked = module::load_modules(ast, sources, &options.module_path, &mut syntax_errors)
+        .map_err(CompileError::from)
+        .and_then(|modules| {
+            semantic::semantic_pass(modules, options).map_err(CompileError::Semantic)
+        });
+    if !syntax_errors.is_empty() {
+        return Err(CompileError::Parse {
+            errors: syntax_errors,
+            checked: checked.err().map(Box::new),
+        });
+    }
+
+    let mut semantic = checked?;
+    let (denied, warnings): (Vec<_>, Vec<_>) = std::mem::take(&mut semantic.warnings)
+        .into_iter()
+        .partition(|warning| options.lint_level(warning.lint()) == LintLevel::Deny);
+    if !denied.is_empty() {
+        return Err(CompileError::Lint { denied, warnings });
+    }
+
+    let binary = backend::backend_pass(semantic, &name, options)?;
+
+    Ok(Compiled { binary, warnings })
+}
+
+#[derive(Debug, Clone)]
+pub struct Compiled {
+    pub binary: Vec<u8>,
+    /// Anything suspicious that didn't stop the binary being made
+    pub warnings: Vec<semantic::SemanticWarning>,
+}
+
+impl Compiled {
+    /// Each warning along with the source it points at and the lint it came from
+    pub fn render_warnings(&self, sources: &SourceMap) -> String {
+        self.warnings
+            .iter()
+            .map(|warning| {
+                let level = format!("warning[{}]", warning.lint());
+                sources.render(&level, warning, Some(warning.span()))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Accept integers as `if` and `while` predicates, like before `Bool` existed
+    pub legacy_truthiness: bool,
+    /// Directories searched in order for imported modules, `a::b` is found at `a/b.son`
+    pub module_path: Vec<PathBuf>,
+    /// Leave out the bounds check on every `a[i]`, for code that's known to stay in bounds
+    pub unchecked_indexing: bool,
+    /// Lints that don't just warn, `#[allow(...)]` in the source still turns them off
+    pub lint_levels: HashMap<Lint, LintLevel>,
+}
+
+impl CompileOptions {
+    pub fn lint_level(&self, lint: Lint) -> LintLevel {
+        self.lint_levels
+            .get(&lint)
+            .copied()
+            .unwrap_or(LintLevel::Warn)
+    }
 }
 
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum CompileError {
+    /// Along with the first error found checking the rest
     #[error("Error while parsing file")]
-    Parse(String),
+    Parse {
+        errors: Vec<ast::SyntaxError>,
+        checked: Option<Box<CompileError>>,
+    },
+    #[error("Error while loading modules")]
+    Module(#[from] module::ModuleError),
+    /// Every error that doesn't just follow from another
     #[error("Error while checking code")]
-    Semantic(#[from] semantic::SemanticError),
+    Semantic(Vec<semantic::SemanticError>),
+    /// Warnings from lints set to deny, along with the ones that only warn
+    #[error("Denied warnings while checking code")]
+    Lint {
+        denied: Vec<semantic::SemanticWarning>,
+        warnings: Vec<semantic::SemanticWarning>,
+    },
     #[error("Error while generating binary code")]
     Backend(#[from] backend::BackendError),
 }
+
+impl CompileError {
+    /// The message along with the source it points at, ready to print
+    pub fn render(&self, sources: &SourceMap) -> String {
+        match self {
+            Self::Parse { errors, checked } => errors
+                .iter()
+                .map(|error| sources.render("error", error, Some(error.span)))
+                .chain(checked.iter().map(|error| error.render(sources)))
+                .collect(),
+            Self::Module(error) => sources.render("error", error, error.span()),
+            Self::Semantic(errors) => errors
+                .iter()
+                .map(|error| sources.render("error", &error.kind, error.span))
+                .collect(),
+            Self::Lint { denied, warnings } => {
+                let warnings = warnings.iter().map(|warning| ("warning", warning));
+                let denied = denied.iter().map(|warning| ("error", warning));
+                warnings
+                    .chain(denied)
+                    .map(|(level, warning)| {
+                        let level = format!("{}[{}]", level, warning.lint());
+                        sources.render(&level, warning, Some(warning.span()))
+                    })
+                    .collect()
+            }
+            Self::Backend(error) => sources.render("error", error, None),
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use {
     anyhow::Result,
     clap::Clap,
-    sonancelang_prototype3::compile,
+    sonancelang_prototype3::{
+        compile,
+        diagnostic::SourceMap,
+        semantic::{Lint, LintLevel},
+        CompileOptions,
+    },
     std::{
+        collections::HashMap,
         env::current_dir,
         fs::{read_to_string, OpenOptions},
         io::Write,
+        path::Path,
+        process::exit,
     },
 };
 
@@ -14,21 +22,79 @@ use {
 struct Options {
     input: String,
     output: String,
+    /// Treat any non-zero integer as true in `if` and `while`
+    #[clap(long)]
+    legacy_truthiness: bool,
+    /// Another directory to look for imported modules in, after the input's own
+    #[clap(long)]
+    module_path: Vec<String>,
+    /// Don't check that indexes are in bounds, faster but an index past the end goes unnoticed
+    #[clap(long)]
+    unchecked_indexing: bool,
+    /// Don't warn for this lint, like `unused_variables`
+    #[clap(long, short = 'A')]
+    allow: Vec<Lint>,
+    /// Warn for this lint, which they all do unless told otherwise
+    #[clap(long, short = 'W')]
+    warn: Vec<Lint>,
+    /// Fail to compile on a warning from this lint, it wins over the other two
+    #[clap(long, short = 'D')]
+    deny: Vec<Lint>,
 }
 
 fn main() -> Result<()> {
     let options = Options::parse();
+    let lint_levels = lint_levels(&options);
     let cwd = current_dir()?;
 
-    let input = read_to_string(cwd.join(options.input))?;
-    let compiled = compile(&input)?;
+    let input_path = cwd.join(&options.input);
+    let input = read_to_string(&input_path)?;
+
+    let module_path = input_path
+        .parent()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .chain(options.module_path.iter().map(|dir| cwd.join(dir)))
+        .collect();
+
+    let mut sources = SourceMap::default();
+    let entry = sources.add(options.input.into(), input);
+
+    let compiled = compile(
+        &mut sources,
+        entry,
+        &CompileOptions {
+            legacy_truthiness: options.legacy_truthiness,
+            module_path,
+            unchecked_indexing: options.unchecked_indexing,
+            lint_levels,
+        },
+    );
+
+    let compiled = match compiled {
+        Ok(compiled) => compiled,
+        Err(error) => {
+            eprint!("{}", error.render(&sources));
+            exit(1);
+        }
+    };
+
+    eprint!("{}", compiled.render_warnings(&sources));
 
     OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
         .open(options.output)?
-        .write_all(&compiled)?;
+        .write_all(&compiled.binary)?;
 
     Ok(())
 }
+
+/// Later levels win, so a lint that's both allowed and denied is denied
+fn lint_levels(options: &Options) -> HashMap<Lint, LintLevel> {
+    let allow = options.allow.iter().map(|&lint| (lint, LintLevel::Allow));
+    let warn = options.warn.iter().map(|&lint| (lint, LintLevel::Warn));
+    let deny = options.deny.iter().map(|&lint| (lint, LintLevel::Deny));
+    allow.chain(warn).chain(deny).collect()
+}
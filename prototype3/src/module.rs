@@ -0,0 +1,159 @@
+use {
+    crate::{
+        ast,
+        diagnostic::{SourceMap, Span},
+    },
+    std::{
+        collections::HashSet,
+        fs::read_to_string,
+        io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The entry file and everything it imports, each module after the ones it imports,
+/// syntax errors in the imported files are pushed to `syntax_errors`
+pub fn load_modules(
+    entry: ast::File,
+    sources: &mut SourceMap,
+    search_path: &[PathBuf],
+    syntax_errors: &mut Vec<ast::SyntaxError>,
+) -> Result<Vec<ast::File>, ModuleError> {
+    let mut loader = Loader {
+        sources,
+        search_path,
+        syntax_errors,
+        loading: entry
+            .module
+            .iter()
+            .map(|m| m.as_string().to_string())
+            .collect(),
+        loaded: HashSet::new(),
+        modules: Vec::new(),
+    };
+
+    loader.visit(entry)?;
+    Ok(loader.modules)
+}
+
+struct Loader<'a> {
+    sources: &'a mut SourceMap,
+    search_path: &'a [PathBuf],
+    syntax_errors: &'a mut Vec<ast::SyntaxError>,
+    /// Modules still waiting on their imports, seeing one again means a cycle
+    loading: Vec<String>,
+    loaded: HashSet<String>,
+    modules: Vec<ast::File>,
+}
+
+impl Loader<'_> {
+    fn visit(&mut self, file: ast::File) -> Result<(), ModuleError> {
+        for import in file.imports.iter() {
+            let module = self.resolve(import)?;
+
+            if self.loading.contains(&module) {
+                return Err(ModuleError::Cycle {
+                    module,
+                    span: import.span,
+                });
+            }
+            if self.loaded.contains(&module) {
+                continue;
+            }
+
+            let imported = self.load(&module)?;
+            self.loading.push(module.clone());
+            self.visit(imported)?;
+            self.loading.pop();
+            self.loaded.insert(module);
+        }
+
+        self.modules.push(file);
+        Ok(())
+    }
+
+    /// Which module an import needs, `geo::Point` could be the module or an item in `geo`
+    fn resolve(&self, import: &ast::Import) -> Result<String, ModuleError> {
+        let path = import.path.as_string();
+
+        let module = match path.rsplit_once("::") {
+            Some((module, _)) if !import.glob && self.find(path).is_none() => module,
+            _ => path,
+        };
+
+        match self.find(module) {
+            Some(_) => Ok(module.to_string()),
+            None => Err(ModuleError::NotFound {
+                module: module.to_string(),
+                span: import.span,
+            }),
+        }
+    }
+
+    fn find(&self, module: &str) -> Option<PathBuf> {
+        self.search_path
+            .iter()
+            .map(|dir| file_path(dir, module))
+            .find(|path| path.is_file())
+    }
+
+    fn load(&mut self, module: &str) -> Result<ast::File, ModuleError> {
+        let path = self.find(module).expect("Module was resolved");
+        let input = read_to_string(&path).map_err(|source| ModuleError::Read {
+            path: path.clone(),
+            source,
+        })?;
+
+        // One that can't be parsed at all is left empty, its errors are reported with the rest
+        let id = self.sources.add(path, input);
+        let mut file =
+            ast::ast_pass(self.sources.text(id), id, self.syntax_errors).unwrap_or_default();
+
+        // A file may say which module it is, but it has to agree with where it was found
+        match &file.module {
+            Some(declared) if declared.as_string() != module => Err(ModuleError::NameMismatch {
+                declared: declared.as_string().to_string(),
+                expected: module.to_string(),
+                span: file.module_span.expect("A declared module has a span"),
+            }),
+            _ => {
+                file.module = Some(ast::Identifier::new(module.to_string()));
+                Ok(file)
+            }
+        }
+    }
+}
+
+fn file_path(dir: &Path, module: &str) -> PathBuf {
+    dir.join(module.replace("::", "/")).with_extension("son")
+}
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ModuleError {
+    #[error("Module {module:?} not found in any directory of the module path")]
+    NotFound { module: String, span: Span },
+    #[error("Module {module:?} ends up importing itself")]
+    Cycle { module: String, span: Span },
+    #[error("File for module {expected:?} declares itself as module {declared:?}")]
+    NameMismatch {
+        declared: String,
+        expected: String,
+        span: Span,
+    },
+    #[error("Couldn't read {path:?}")]
+    Read { path: PathBuf, source: io::Error },
+}
+
+impl ModuleError {
+    /// The import that couldn't be loaded
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::NotFound { span, .. }
+            | Self::Cycle { span, .. }
+            | Self::NameMismatch { span, .. } => Some(*span),
+            Self::Read { .. } => None,
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use super::ast;
+use {
+    super::{ast, SemanticError, SemanticErrorKind, SymbolId, SymbolTable},
+    std::{collections::HashMap, fmt},
+};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Scope {
     Public,
+    Shared,
     Local,
 }
 
@@ -11,12 +15,13 @@ impl ast::Scope {
         use Scope::*;
         match self {
             Self::Local => Local,
+            Self::Shared => Shared,
             Self::Public => Public,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Ty {
     I8,
     I16,
@@ -30,13 +35,355 @@ pub enum Ty {
     USize,
     F32,
     F64,
+    Bool,
+    /// Has no data, so it takes no space and is never passed or returned
+    Unit,
+    /// The type of anything that jumps away, like `return`, it has no values at all
+    Never,
+    Pointer {
+        mutable: bool,
+        to: Box<Ty>,
+    },
+    /// Elements one after another, passed around by value like a struct
+    Array {
+        of: Box<Ty>,
+        len: u32,
+    },
+    /// Where some elements start and how many there are, only `[mut T]` can change them
+    Slice {
+        mutable: bool,
+        of: Box<Ty>,
+    },
+    /// The address of a function, called with the same ABI as C
+    Func {
+        params: Vec<Ty>,
+        ret: Box<Ty>,
+    },
+    Struct {
+        name: Identifier,
+        symbol_id: SymbolId,
+        args: Vec<Ty>,
+    },
+    Union {
+        name: Identifier,
+        symbol_id: SymbolId,
+        args: Vec<Ty>,
+    },
+    /// Points at any value with an impl of the trait, along with the vtable of that impl
+    Dyn {
+        mutable: bool,
+        name: Identifier,
+        symbol_id: SymbolId,
+    },
+    /// Stands in for a type argument, only found in generic signatures
+    Param(Identifier),
+    /// Given to whatever failed to check, its error is already reported
+    /// and it fits anywhere, so it doesn't cause any more
+    Error,
 }
 
-impl ast::Ty {
-    pub fn visit_common(self) -> Ty {
+/// Type arguments nested deeper than this are assumed to grow forever
+pub const MAX_TY_DEPTH: usize = 32;
+
+/// A type this deep can only come from one that expands itself forever. It's well past
+/// `MAX_TY_DEPTH`, so a generic function's body building on its type arguments isn't mistaken for one
+pub const MAX_LAYOUT_DEPTH: usize = 2 * MAX_TY_DEPTH;
+
+/// What each type parameter has been found to be
+pub type Substitution = HashMap<Identifier, Ty>;
+
+impl Ty {
+    /// What a value of either type can be used as, `Never` fits anywhere since it's never there
+    pub fn join(&self, other: &Ty) -> Option<Ty> {
+        match (self, other) {
+            (Ty::Never | Ty::Error, ty) | (ty, Ty::Never | Ty::Error) => Some(ty.clone()),
+            (left, right) if left == right => Some(left.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        use Ty::*;
+        matches!(
+            self,
+            I8 | I16 | I32 | I64 | ISize | U8 | U16 | U32 | U64 | USize
+        )
+    }
+
+    pub fn is_signed(&self) -> bool {
         use Ty::*;
+        matches!(self, I8 | I16 | I32 | I64 | ISize)
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Ty::F32 | Ty::F64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Ty::Pointer { .. })
+    }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self, Ty::Struct { .. })
+    }
+
+    /// Structs, unions, arrays, slices and trait objects, which always live in memory
+    pub fn is_aggregate(&self) -> bool {
+        matches!(
+            self,
+            Ty::Struct { .. }
+                | Ty::Union { .. }
+                | Ty::Array { .. }
+                | Ty::Slice { .. }
+                | Ty::Dyn { .. }
+        )
+    }
+
+    /// Type found behind a pointer, if this is one
+    pub fn pointee(&self) -> Option<&Ty> {
+        match self {
+            Ty::Pointer { to, .. } => Some(to),
+            _ => None,
+        }
+    }
 
+    /// Whether any type parameter is still waiting to be replaced
+    pub fn has_params(&self) -> bool {
         match self {
+            Ty::Param(_) => true,
+            Ty::Pointer { to, .. } | Ty::Array { of: to, .. } | Ty::Slice { of: to, .. } => {
+                to.has_params()
+            }
+            Ty::Struct { args, .. } | Ty::Union { args, .. } => args.iter().any(Ty::has_params),
+            Ty::Func { params, ret } => params.iter().any(Ty::has_params) || ret.has_params(),
+            _ => false,
+        }
+    }
+
+    /// Whether it's made from a type that failed to check
+    pub fn has_error(&self) -> bool {
+        match self {
+            Ty::Error => true,
+            Ty::Pointer { to, .. } | Ty::Array { of: to, .. } | Ty::Slice { of: to, .. } => {
+                to.has_error()
+            }
+            Ty::Struct { args, .. } | Ty::Union { args, .. } => args.iter().any(Ty::has_error),
+            Ty::Func { params, ret } => params.iter().any(Ty::has_error) || ret.has_error(),
+            _ => false,
+        }
+    }
+
+    /// How many types are nested inside each other, `I32` is 1 and `*I32` is 2
+    pub fn depth(&self) -> usize {
+        match self {
+            Ty::Pointer { to, .. } | Ty::Array { of: to, .. } | Ty::Slice { of: to, .. } => {
+                1 + to.depth()
+            }
+            Ty::Struct { args, .. } | Ty::Union { args, .. } => {
+                1 + args.iter().map(Ty::depth).max().unwrap_or(0)
+            }
+            Ty::Func { params, ret } => {
+                1 + params
+                    .iter()
+                    .map(Ty::depth)
+                    .max()
+                    .unwrap_or(0)
+                    .max(ret.depth())
+            }
+            _ => 1,
+        }
+    }
+
+    /// Replace every known type parameter, unknown ones are left as they are
+    pub fn substitute(&self, substitution: &Substitution) -> Ty {
+        match self {
+            Ty::Param(name) => substitution
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            Ty::Pointer { mutable, to } => Ty::Pointer {
+                mutable: *mutable,
+                to: Box::new(to.substitute(substitution)),
+            },
+            Ty::Array { of, len } => Ty::Array {
+                of: Box::new(of.substitute(substitution)),
+                len: *len,
+            },
+            Ty::Slice { mutable, of } => Ty::Slice {
+                mutable: *mutable,
+                of: Box::new(of.substitute(substitution)),
+            },
+            Ty::Func { params, ret } => Ty::Func {
+                params: params.iter().map(|p| p.substitute(substitution)).collect(),
+                ret: Box::new(ret.substitute(substitution)),
+            },
+            Ty::Struct {
+                name,
+                symbol_id,
+                args,
+            } => Ty::Struct {
+                name: name.clone(),
+                symbol_id: *symbol_id,
+                args: args.iter().map(|a| a.substitute(substitution)).collect(),
+            },
+            Ty::Union {
+                name,
+                symbol_id,
+                args,
+            } => Ty::Union {
+                name: name.clone(),
+                symbol_id: *symbol_id,
+                args: args.iter().map(|a| a.substitute(substitution)).collect(),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// Check that `found` fits this type, learning what type parameters have to be along the way
+    pub fn unify(&self, found: &Ty, substitution: &mut Substitution) -> bool {
+        match (self, found) {
+            (Ty::Param(name), found) => match substitution.get(name) {
+                Some(known) => known == found,
+                None => {
+                    substitution.insert(name.clone(), found.clone());
+                    true
+                }
+            },
+            (Ty::Error, _) | (_, Ty::Error) => true,
+            (
+                Ty::Pointer { mutable, to },
+                Ty::Pointer {
+                    mutable: found_mutable,
+                    to: found_to,
+                },
+            ) => mutable == found_mutable && to.unify(found_to, substitution),
+            (
+                Ty::Array { of, len },
+                Ty::Array {
+                    of: found_of,
+                    len: found_len,
+                },
+            ) => len == found_len && of.unify(found_of, substitution),
+            (
+                Ty::Slice { mutable, of },
+                Ty::Slice {
+                    mutable: found_mutable,
+                    of: found_of,
+                },
+            ) => mutable == found_mutable && of.unify(found_of, substitution),
+            (
+                Ty::Func { params, ret },
+                Ty::Func {
+                    params: found_params,
+                    ret: found_ret,
+                },
+            ) => {
+                params.len() == found_params.len()
+                    && params
+                        .iter()
+                        .zip(found_params)
+                        .all(|(p, f)| p.unify(f, substitution))
+                    && ret.unify(found_ret, substitution)
+            }
+            (
+                Ty::Struct {
+                    symbol_id, args, ..
+                },
+                Ty::Struct {
+                    symbol_id: found_id,
+                    args: found_args,
+                    ..
+                },
+            )
+            | (
+                Ty::Union {
+                    symbol_id, args, ..
+                },
+                Ty::Union {
+                    symbol_id: found_id,
+                    args: found_args,
+                    ..
+                },
+            ) => {
+                symbol_id == found_id
+                    && args.len() == found_args.len()
+                    && args
+                        .iter()
+                        .zip(found_args)
+                        .all(|(a, f)| a.unify(f, substitution))
+            }
+            (expected, found) => expected == found,
+        }
+    }
+
+    /// Size and alignment of anything but an aggregate, those need their definition
+    pub fn scalar_layout(&self) -> Option<Layout> {
+        use Ty::*;
+        let size = match self {
+            Unit | Never | Error => return Some(Layout::default()),
+            I8 | U8 | Bool => 1,
+            I16 | U16 => 2,
+            I32 | U32 | F32 => 4,
+            I64 | U64 | F64 => 8,
+            ISize | USize | Pointer { .. } | Func { .. } => target_lexicon::HOST
+                .pointer_width()
+                .expect("Host should have a pointer width")
+                .bytes() as u32,
+            Array { .. } | Slice { .. } | Dyn { .. } | Struct { .. } | Union { .. } | Param(_) => {
+                return None
+            }
+        };
+        Some(Layout { size, align: size })
+    }
+}
+
+/// Where a value lives in memory, following the C rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u32,
+    pub align: u32,
+}
+
+impl Layout {
+    /// A slice is a pointer to its first element followed by a `USize` count,
+    /// a trait object has the same shape with a pointer to its vtable instead
+    pub fn slice() -> Self {
+        let pointer = Ty::USize.scalar_layout().expect("USize is a scalar");
+        Layout {
+            size: pointer.size * 2,
+            align: pointer.align,
+        }
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { size: 0, align: 1 }
+    }
+}
+
+impl ast::Ty {
+    /// A type that can't be found is reported, and checking carries on with `Ty::Error`
+    pub fn visit_common(self, symbol_table: &SymbolTable) -> Ty {
+        let span = self.span;
+        self.kind
+            .visit_common(symbol_table)
+            .unwrap_or_else(|error| {
+                symbol_table.report(error.at(span));
+                Ty::Error
+            })
+    }
+}
+
+impl ast::TyKind {
+    fn visit_common(self, symbol_table: &SymbolTable) -> Result<Ty, SemanticError> {
+        use Ty::*;
+
+        Ok(match self {
             Self::I8 => I8,
             Self::I16 => I16,
             Self::I32 => I32,
@@ -49,10 +396,174 @@ impl ast::Ty {
             Self::USize => USize,
             Self::F32 => F32,
             Self::F64 => F64,
+            Self::Bool => Bool,
+            Self::Unit => Unit,
+            Self::Never => Never,
+            Self::Pointer { mutable, to } => Pointer {
+                mutable,
+                to: Box::new(to.visit_common(symbol_table)),
+            },
+            Self::Array { of, len } => Array {
+                of: Box::new(of.visit_common(symbol_table)),
+                len,
+            },
+            Self::Slice { mutable, of } => Slice {
+                mutable,
+                of: Box::new(of.visit_common(symbol_table)),
+            },
+            Self::Func { params, ret } => Func {
+                params: params
+                    .into_iter()
+                    .map(|p| p.visit_common(symbol_table))
+                    .collect(),
+                ret: Box::new(ret.visit_common(symbol_table)),
+            },
+            Self::Dyn { mutable, name } => {
+                let name = name.visit_common();
+                let symbol =
+                    symbol_table
+                        .get(&name)
+                        .ok_or_else(|| SemanticErrorKind::TraitNotFound {
+                            symbol: name.clone(),
+                        })?;
+                let def =
+                    symbol
+                        .as_trait()
+                        .ok_or_else(|| SemanticErrorKind::ExpectedTraitSymbol {
+                            symbol: name.clone(),
+                        })?;
+
+                Dyn {
+                    mutable,
+                    name: def.name.clone(),
+                    symbol_id: symbol.id(),
+                }
+            }
+            Self::Named { name, args } => {
+                let name = name.visit_common();
+                let args = args
+                    .into_iter()
+                    .map(|a| a.visit_common(symbol_table))
+                    .collect::<Vec<_>>();
+
+                let symbol =
+                    symbol_table
+                        .get(&name)
+                        .ok_or_else(|| SemanticErrorKind::TyNotFound {
+                            symbol: name.clone(),
+                        })?;
+
+                // Named after where the type was declared, not how it was imported
+                let symbol_id = symbol.id();
+                let (ty_params, ty) = if let Some(ty) = symbol.as_ty() {
+                    (&[][..], ty.clone())
+                } else if let Some(def) = symbol.as_struct() {
+                    let ty = Struct {
+                        name: def.name.clone(),
+                        symbol_id,
+                        args: args.clone(),
+                    };
+                    (&def.ty_params[..], ty)
+                } else if let Some(def) = symbol.as_union() {
+                    let ty = Union {
+                        name: def.name.clone(),
+                        symbol_id,
+                        args: args.clone(),
+                    };
+                    (&def.ty_params[..], ty)
+                } else {
+                    return Err(SemanticErrorKind::ExpectedTySymbol { symbol: name }.into());
+                };
+
+                if args.len() != ty_params.len() {
+                    return Err(SemanticErrorKind::WrongTyArgCount {
+                        name,
+                        expected: ty_params.len(),
+                        found: args.len(),
+                    }
+                    .into());
+                }
+
+                ty
+            }
+        })
+    }
+}
+
+/// Written the same way as in source, also used to give each generic instance its own name
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Ty::*;
+        match self {
+            I8 => write!(f, "I8"),
+            I16 => write!(f, "I16"),
+            I32 => write!(f, "I32"),
+            I64 => write!(f, "I64"),
+            ISize => write!(f, "ISize"),
+            U8 => write!(f, "U8"),
+            U16 => write!(f, "U16"),
+            U32 => write!(f, "U32"),
+            U64 => write!(f, "U64"),
+            USize => write!(f, "USize"),
+            F32 => write!(f, "F32"),
+            F64 => write!(f, "F64"),
+            Bool => write!(f, "Bool"),
+            Unit => write!(f, "Unit"),
+            Never => write!(f, "Never"),
+            Pointer { mutable: true, to } => write!(f, "*mut {}", to),
+            Pointer { mutable: false, to } => write!(f, "*{}", to),
+            Array { of, len } => write!(f, "[{}; {}]", of, len),
+            Slice { mutable: true, of } => write!(f, "[mut {}]", of),
+            Slice { mutable: false, of } => write!(f, "[{}]", of),
+            Dyn {
+                mutable: true,
+                name,
+                ..
+            } => write!(f, "dyn mut {}", name.as_string()),
+            Dyn {
+                mutable: false,
+                name,
+                ..
+            } => write!(f, "dyn {}", name.as_string()),
+            Func { params, ret } => {
+                write!(f, "func(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") -> {}", ret)
+            }
+            Struct { name, args, .. } | Union { name, args, .. } => {
+                write!(f, "{}{}", name.as_string(), TyArgs(args))
+            }
+            Param(name) => write!(f, "{}", name.as_string()),
+            Error => write!(f, "{{error}}"),
         }
     }
 }
 
+/// Type arguments in brackets, or nothing at all if there are none
+pub struct TyArgs<'a>(pub &'a [Ty]);
+
+impl fmt::Display for TyArgs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, "[")?;
+        for (i, arg) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, "]")
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Identifier(String);
 
@@ -68,6 +579,24 @@ impl Identifier {
     pub fn as_string(&self) -> &str {
         &self.0
     }
+
+    /// `geo::shapes::Point` splits into `geo` and `shapes::Point`
+    pub fn split_first(&self) -> Option<(Identifier, Identifier)> {
+        let (first, rest) = self.0.split_once("::")?;
+        Some((
+            Identifier::new(first.to_string()),
+            Identifier::new(rest.to_string()),
+        ))
+    }
+
+    /// `geo::shapes::Point` splits into `geo::shapes` and `Point`
+    pub fn split_last(&self) -> Option<(Identifier, Identifier)> {
+        let (rest, last) = self.0.rsplit_once("::")?;
+        Some((
+            Identifier::new(rest.to_string()),
+            Identifier::new(last.to_string()),
+        ))
+    }
 }
 
 impl ast::Identifier {
@@ -0,0 +1,334 @@
+use {super::*, std::collections::HashSet};
+
+/// Types can refer to each other in any order, so every name gets a symbol before any field is resolved,
+/// a duplicate field or variant is reported and left out
+pub fn visit_types(
+    structs: Vec<ast::Struct>,
+    unions: Vec<ast::Union>,
+    symbol_table: &mut SymbolTable,
+) -> (Vec<Struct>, Vec<Union>) {
+    let structs = structs
+        .into_iter()
+        .map(|s| {
+            let name = s.name.visit_common();
+            let ty_params = visit_ty_params(s.ty_params);
+            let symbol = Symbol::new_struct(symbol_table.qualify(&name), ty_params.clone());
+            let symbol_id = symbol_table.set(name.clone(), symbol);
+            (name, symbol_id, ty_params, s.fields, s.span)
+        })
+        .collect::<Vec<_>>();
+
+    let unions = unions
+        .into_iter()
+        .map(|u| {
+            let name = u.name.visit_common();
+            let ty_params = visit_ty_params(u.ty_params);
+            let symbol = Symbol::new_union(symbol_table.qualify(&name), ty_params.clone());
+            let symbol_id = symbol_table.set(name.clone(), symbol);
+            (name, symbol_id, ty_params, u.variants, u.span)
+        })
+        .collect::<Vec<_>>();
+
+    let mut order = Vec::with_capacity(structs.len() + unions.len());
+
+    for (name, symbol_id, ty_params, fields, span) in structs {
+        let fields = {
+            let symbol_table = &mut symbol_table.fork();
+            bind_ty_params(&ty_params, symbol_table);
+
+            let mut seen = HashSet::new();
+            fields
+                .into_iter()
+                .filter_map(|f| {
+                    let field = f.name.visit_common();
+                    if !seen.insert(field.clone()) {
+                        symbol_table.report(
+                            SemanticError::from(SemanticErrorKind::DuplicateField {
+                                name: name.clone(),
+                                field,
+                            })
+                            .at(f.span),
+                        );
+                        return None;
+                    }
+                    Some((field, f.ty.visit_common(symbol_table)))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let symbol = symbol_table.get_mut(&name).expect("Type was declared");
+        let def = symbol.as_struct_mut().expect("Type was declared");
+        def.fields = fields;
+
+        order.push((def.ty(symbol_id), ty_params.is_empty(), span));
+
+        let symbol = symbol.clone();
+        symbol_table.set_ty_def(symbol);
+    }
+
+    for (name, symbol_id, ty_params, variants, span) in unions {
+        let union = Ty::Union {
+            name: symbol_table.qualify(&name),
+            symbol_id,
+            args: ty_params.iter().cloned().map(Ty::Param).collect(),
+        };
+
+        let variants = variants
+            .into_iter()
+            .enumerate()
+            .map(|(tag, v)| {
+                let variant = v.name.visit_common();
+
+                // Variants are used without their union, so they can't be shared,
+                // one that is still takes up its tag but keeps the name it had
+                if symbol_table.get(&variant).is_some() {
+                    symbol_table.report(
+                        SemanticError::from(SemanticErrorKind::DuplicateVariant {
+                            variant: variant.clone(),
+                        })
+                        .at(v.span),
+                    );
+                } else {
+                    symbol_table.set(
+                        variant.clone(),
+                        Symbol::new_variant(union.clone(), tag as u32),
+                    );
+                }
+
+                let symbol_table = &mut symbol_table.fork();
+                bind_ty_params(&ty_params, symbol_table);
+
+                let fields = v
+                    .fields
+                    .into_iter()
+                    .map(|ty| ty.visit_common(symbol_table))
+                    .collect::<Vec<_>>();
+                (variant, fields)
+            })
+            .collect::<Vec<_>>();
+
+        let symbol = symbol_table.get_mut(&name).expect("Type was declared");
+        symbol.as_union_mut().expect("Type was declared").variants = variants;
+
+        let symbol = symbol.clone();
+        symbol_table.set_ty_def(symbol);
+
+        order.push((union, ty_params.is_empty(), span));
+    }
+
+    let mut structs = Vec::new();
+    let mut unions = Vec::new();
+
+    // Generic types are laid out once for every set of arguments they get used with
+    for (ty, _, span) in order.into_iter().filter(|(_, concrete, _)| *concrete) {
+        let info = match symbol_table.ty_info(&ty) {
+            Ok(info) => info,
+            Err(error) => {
+                symbol_table.report(error.at(span));
+                continue;
+            }
+        };
+        match (info, ty) {
+            (
+                TyInfo::Struct(info),
+                Ty::Struct {
+                    name, symbol_id, ..
+                },
+            ) => structs.push(Struct {
+                name,
+                fields: info.fields,
+                layout: info.layout,
+                symbol_id,
+                span,
+            }),
+            (
+                TyInfo::Union(info),
+                Ty::Union {
+                    name, symbol_id, ..
+                },
+            ) => unions.push(Union {
+                name,
+                variants: info.variants,
+                layout: info.layout,
+                symbol_id,
+                span,
+            }),
+            _ => unreachable!("Info matches the type"),
+        }
+    }
+
+    (structs, unions)
+}
+
+impl SymbolTable<'_> {
+    /// Size and alignment of any type, aggregates are laid out the first time they're needed
+    pub fn ty_layout(&self, ty: &Ty) -> Result<Layout, SemanticError> {
+        lay_out(ty, self, &mut HashSet::new())
+    }
+
+    pub fn ty_info(&self, ty: &Ty) -> Result<TyInfo, SemanticError> {
+        self.ty_layout(ty)?;
+        Ok(self.get_laid_out(ty).expect("Type was just laid out"))
+    }
+
+    pub fn struct_info(&self, ty: &Ty) -> Result<StructInfo, SemanticError> {
+        match self.ty_info(ty)? {
+            TyInfo::Struct(info) => Ok(info),
+            TyInfo::Union(_) => unreachable!("Only asked for with a struct type"),
+        }
+    }
+
+    pub fn union_info(&self, ty: &Ty) -> Result<UnionInfo, SemanticError> {
+        match self.ty_info(ty)? {
+            TyInfo::Union(info) => Ok(info),
+            TyInfo::Struct(_) => unreachable!("Only asked for with a union type"),
+        }
+    }
+}
+
+/// Place fields in order, padding each to its alignment like a C compiler would
+fn lay_out(
+    ty: &Ty,
+    symbol_table: &SymbolTable,
+    visiting: &mut HashSet<Ty>,
+) -> Result<Layout, SemanticError> {
+    let (name, symbol_id, args) = match ty {
+        Ty::Struct {
+            name,
+            symbol_id,
+            args,
+        }
+        | Ty::Union {
+            name,
+            symbol_id,
+            args,
+        } => (name, *symbol_id, args),
+        Ty::Array { of, len } => {
+            let layout = lay_out(of, symbol_table, visiting)?;
+            let size = layout
+                .size
+                .checked_mul(*len)
+                .ok_or_else(|| SemanticErrorKind::ArrayTooLarge { ty: ty.clone() })?;
+            return Ok(Layout {
+                size,
+                align: layout.align,
+            });
+        }
+        Ty::Slice { .. } | Ty::Dyn { .. } => return Ok(Layout::slice()),
+        ty => {
+            return Ok(ty
+                .scalar_layout()
+                .expect("Only aggregates lack a scalar layout"))
+        }
+    };
+
+    if let Some(info) = symbol_table.get_laid_out(ty) {
+        return Ok(info.layout());
+    }
+
+    // Only a pointer can break the cycle, otherwise the size would be infinite
+    if ty.depth() > MAX_LAYOUT_DEPTH || !visiting.insert(ty.clone()) {
+        return Err(SemanticErrorKind::RecursiveStruct { name: name.clone() }.into());
+    }
+
+    let symbol = symbol_table
+        .get_ty_def(symbol_id)
+        .expect("Type was declared");
+
+    let info = if let Some(def) = symbol.as_struct() {
+        let substitution = substitution(&def.ty_params, args);
+        let fields = def
+            .fields
+            .iter()
+            .map(|(name, ty)| (name.clone(), ty.substitute(&substitution)));
+
+        let (fields, layout) = lay_out_fields(fields, symbol_table, visiting)?;
+        TyInfo::Struct(StructInfo { fields, layout })
+    } else {
+        let def = symbol.as_union().expect("Type was declared");
+        let substitution = substitution(&def.ty_params, args);
+
+        let tag = Ty::U32.scalar_layout().expect("Tag is a scalar");
+        let mut payloads = Vec::with_capacity(def.variants.len());
+        let mut payload = Layout::default();
+
+        for (variant, fields) in def.variants.iter() {
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| (Identifier::new(i.to_string()), ty.substitute(&substitution)));
+            let (fields, layout) = lay_out_fields(fields, symbol_table, visiting)?;
+
+            payload.size = payload.size.max(layout.size);
+            payload.align = payload.align.max(layout.align);
+            payloads.push((variant.clone(), fields));
+        }
+
+        // Every payload starts at the same place, right after the tag
+        let start = align_to(tag.size, payload.align);
+        let align = tag.align.max(payload.align);
+        let layout = Layout {
+            size: align_to(start + payload.size, align),
+            align,
+        };
+
+        TyInfo::Union(UnionInfo {
+            variants: payloads
+                .into_iter()
+                .map(|(name, fields)| VariantInfo {
+                    name,
+                    fields: fields
+                        .into_iter()
+                        .map(|f| FieldInfo {
+                            offset: f.offset + start,
+                            ..f
+                        })
+                        .collect(),
+                })
+                .collect(),
+            layout,
+        })
+    };
+
+    let layout = info.layout();
+    visiting.remove(ty);
+    symbol_table.set_laid_out(ty.clone(), info);
+
+    Ok(layout)
+}
+
+fn lay_out_fields(
+    fields: impl Iterator<Item = (Identifier, Ty)>,
+    symbol_table: &SymbolTable,
+    visiting: &mut HashSet<Ty>,
+) -> Result<(Vec<FieldInfo>, Layout), SemanticError> {
+    let mut layout = Layout::default();
+    let mut infos = Vec::new();
+
+    for (name, ty) in fields {
+        let field_layout = lay_out(&ty, symbol_table, visiting)?;
+
+        let offset = align_to(layout.size, field_layout.align);
+        infos.push(FieldInfo { name, ty, offset });
+
+        layout.size = offset + field_layout.size;
+        layout.align = layout.align.max(field_layout.align);
+    }
+
+    // Trailing padding keeps every element of an array aligned
+    layout.size = align_to(layout.size, layout.align);
+
+    Ok((infos, layout))
+}
+
+fn substitution(ty_params: &[Identifier], args: &[Ty]) -> Substitution {
+    ty_params
+        .iter()
+        .cloned()
+        .zip(args.iter().cloned())
+        .collect()
+}
+
+fn align_to(offset: u32, align: u32) -> u32 {
+    offset.div_ceil(align) * align
+}
@@ -1,37 +1,268 @@
+mod layout;
 pub mod structure;
 
-pub use {super::*, structure::*};
+pub use {super::*, std::collections::HashMap, structure::*};
 
 impl ast::File {
-    pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<File, SemanticError> {
-        Ok(File {
-            items: self
-                .items
+    /// Imported modules must already be checked, the loader puts them first.
+    /// An item with an error is reported and left out, the rest are still declared
+    pub fn visit_ast(
+        self,
+        modules: &HashMap<Identifier, ModuleInfo>,
+        symbol_table: &mut SymbolTable,
+    ) -> File {
+        for import in self.imports {
+            let span = import.span;
+            if let Err(error) = import.visit_ast(modules, symbol_table) {
+                symbol_table.report(error.at(span));
+            }
+        }
+
+        for attribute in self.attributes {
+            let span = attribute.span;
+            if let Err(error) = attribute.visit_ast(symbol_table) {
+                symbol_table.report(error.at(span));
+            }
+        }
+
+        let exports = exports(&self.items);
+        let mut structs = Vec::new();
+        let mut unions = Vec::new();
+        let mut traits = Vec::new();
+        let mut impls = Vec::new();
+        let mut items = Vec::new();
+
+        for item in self.items {
+            match item {
+                ast::Item::Struct(item) => structs.push(item),
+                ast::Item::Union(item) => unions.push(item),
+                ast::Item::Trait(item) => traits.push(item),
+                ast::Item::Impl(item) => impls.push(item),
+                item => items.push(item),
+            }
+        }
+
+        // A field can be a `dyn Trait`, which only needs the trait's name to be known
+        let traits = traits
+            .into_iter()
+            .map(|t| {
+                let name = t.name.visit_common();
+                let symbol = Symbol::new_trait(symbol_table.qualify(&name));
+                symbol_table.set(name.clone(), symbol);
+                (name, t.methods, t.span)
+            })
+            .collect::<Vec<_>>();
+
+        // Functions need every type laid out to know their signatures
+        let (structs, unions) = layout::visit_types(structs, unions, symbol_table);
+
+        for (name, methods, span) in traits {
+            if let Err(error) = visit_trait(&name, methods, symbol_table) {
+                symbol_table.report(error.at(span));
+            }
+        }
+
+        // Methods are declared before any body, so they can be called from anywhere in the file
+        let mut methods = Vec::new();
+        for item in impls {
+            let span = item.span;
+            match item.visit_ast(symbol_table) {
+                Ok(checked) => methods.extend(checked),
+                Err(error) => symbol_table.report(error.at(span)),
+            }
+        }
+
+        let items = items
+            .into_iter()
+            .filter_map(|item| {
+                item.visit_ast(symbol_table)
+                    .map_err(|error| symbol_table.report(error))
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+
+        File {
+            items: structs
                 .into_iter()
-                .map(|i| i.visit_ast(symbol_table))
-                .collect::<Result<_, _>>()?,
-        })
+                .map(Item::Struct)
+                .chain(unions.into_iter().map(Item::Union))
+                .chain(methods.into_iter().map(Item::Function))
+                .chain(items)
+                .collect(),
+            exports,
+        }
+    }
+}
+
+/// Types every module can use without importing them, they live in the root table
+pub fn visit_prelude(symbol_table: &mut SymbolTable) -> Vec<Struct> {
+    let name = |name: &str| ast::Identifier::new(name.to_string());
+
+    // What a `"..."` literal gives, the bytes are followed by a NUL that `len` leaves out
+    // It isn't written anywhere, so it can't fail and has nothing to point at
+    let span = Span::default();
+    let ty = |kind| ast::Ty { kind, span };
+    let str = ast::Struct {
+        scope: ast::Scope::Shared,
+        name: name("Str"),
+        ty_params: Vec::new(),
+        fields: vec![
+            ast::Field {
+                name: name("ptr"),
+                ty: ty(ast::TyKind::Pointer {
+                    mutable: false,
+                    to: Box::new(ty(ast::TyKind::U8)),
+                }),
+                span,
+            },
+            ast::Field {
+                name: name("len"),
+                ty: ty(ast::TyKind::USize),
+                span,
+            },
+        ],
+        span,
+    };
+
+    let (structs, _) = layout::visit_types(vec![str], Vec::new(), symbol_table);
+    structs
+}
+
+/// Names other modules can import, variants go along with their union
+fn exports(items: &[ast::Item]) -> Vec<Identifier> {
+    let mut exports = Vec::new();
+
+    for item in items {
+        match item {
+            ast::Item::Function(func) if !matches!(func.scope, ast::Scope::Local) => {
+                exports.push(func.name.clone().visit_common());
+            }
+            ast::Item::Const(item) if matches!(item.scope, ast::Scope::Shared) => {
+                exports.push(item.name.clone().visit_common());
+            }
+            ast::Item::Static(item) if !matches!(item.scope, ast::Scope::Local) => {
+                exports.push(item.name.clone().visit_common());
+            }
+            ast::Item::Struct(item) if matches!(item.scope, ast::Scope::Shared) => {
+                exports.push(item.name.clone().visit_common());
+            }
+            ast::Item::Trait(item) if matches!(item.scope, ast::Scope::Shared) => {
+                exports.push(item.name.clone().visit_common());
+            }
+            ast::Item::Union(item) if matches!(item.scope, ast::Scope::Shared) => {
+                exports.push(item.name.clone().visit_common());
+                exports.extend(item.variants.iter().map(|v| v.name.clone().visit_common()));
+            }
+            _ => {}
+        }
+    }
+
+    exports
+}
+
+impl ast::Import {
+    pub fn visit_ast(
+        self,
+        modules: &HashMap<Identifier, ModuleInfo>,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<(), SemanticError> {
+        let path = self.path.visit_common();
+
+        if self.glob {
+            let module = &modules[&path];
+            for (name, symbol) in module.exports.iter() {
+                symbol_table.set(name.clone(), symbol.clone());
+            }
+            return Ok(());
+        }
+
+        // The module itself, used through paths like `geo::Point`
+        if let Some(module) = modules.get(&path) {
+            let name = path.split_last().map_or(path.clone(), |(_, name)| name);
+            symbol_table.set(name, Symbol::new_module(module.clone()));
+            return Ok(());
+        }
+
+        let (module, name) = path.split_last().expect("Loader found the module");
+        let module = &modules[&module];
+
+        match module.exports.get(&name) {
+            Some(symbol) => {
+                symbol_table.set(name, symbol.clone());
+                Ok(())
+            }
+            None if module.private.contains(&name) => {
+                Err(SemanticErrorKind::PrivateImport { path }.into())
+            }
+            None => Err(SemanticErrorKind::ImportNotFound { path }.into()),
+        }
     }
 }
 
 impl ast::Item {
     pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<Item, SemanticError> {
         use Item::*;
-        Ok(match self {
-            Self::Declare(declare) => Declare(declare.visit_ast(symbol_table)?),
-            Self::Function(func) => Function(func.visit_ast(symbol_table)?),
-        })
+        let span = self.span();
+        let item = match self {
+            Self::Declare(declare) => declare.visit_ast(symbol_table).map(Declare),
+            Self::Function(func) => func.visit_ast(symbol_table).map(Function),
+            Self::Const(item) => item.visit_ast(symbol_table).map(Const),
+            Self::Static(item) => item.visit_ast(symbol_table).map(Static),
+            Self::Struct(_) | Self::Union(_) | Self::Trait(_) | Self::Impl(_) => {
+                unreachable!("Types and their methods are declared up front")
+            }
+        };
+        item.map_err(|error| error.at(span))
     }
 }
 
 impl ast::DeclareBlock {
     pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<DeclareBlock, SemanticError> {
-        Ok(DeclareBlock {
-            functions: self
-                .functions
-                .into_iter()
-                .map(|f| f.visit_ast(symbol_table))
-                .collect::<Result<_, _>>()?,
+        let mut block = DeclareBlock {
+            functions: Vec::new(),
+            statics: Vec::new(),
+            span: self.span,
+        };
+
+        // A bad item is reported and left out, the rest of the block is still declared
+        for item in self.items {
+            match item {
+                ast::DeclareItem::Function(func) => {
+                    let span = func.span;
+                    match func.visit_ast(symbol_table) {
+                        Ok(func) => block.functions.push(func),
+                        Err(error) => symbol_table.report(error.at(span)),
+                    }
+                }
+                ast::DeclareItem::Static(item) => {
+                    let span = item.span;
+                    match item.visit_ast(symbol_table) {
+                        Ok(item) => block.statics.push(item),
+                        Err(error) => symbol_table.report(error.at(span)),
+                    }
+                }
+            }
+        }
+
+        Ok(block)
+    }
+}
+
+impl ast::DeclareStatic {
+    pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<DeclareStatic, SemanticError> {
+        let name = self.name.visit_common();
+        let mut ty = self.ty.visit_common(symbol_table);
+        lay_out_or_error(&mut ty, self.span, symbol_table);
+
+        let symbol_id =
+            symbol_table.set(name.clone(), Symbol::new_static(ty.clone(), self.mutable));
+
+        Ok(DeclareStatic {
+            mutable: self.mutable,
+            name,
+            ty,
+            symbol_id,
+            span: self.span,
         })
     }
 }
@@ -42,24 +273,30 @@ impl ast::DeclareFunction {
         symbol_table: &mut SymbolTable,
     ) -> Result<DeclareFunction, SemanticError> {
         let name = self.name.visit_common();
-        let ty = self.ty.visit_common();
+        let mut ty = self.ty.visit_common(symbol_table);
 
-        let params = self
+        let mut params = self
             .params
             .into_iter()
             .map(|a| a.visit_ast(symbol_table))
             .collect::<Result<Vec<_>, _>>()?;
 
-        let symbol_id = symbol_table.set(
-            name.clone(),
-            Symbol::new_func(ty, params.iter().map(|p| p.ty).collect()),
-        );
+        lay_out_signature_or_error(&mut params, &mut ty, self.span, symbol_table);
+
+        let param_tys = params.iter().map(|p| p.ty.clone()).collect();
+        let symbol = if self.variadic {
+            Symbol::new_variadic_func(ty.clone(), param_tys)
+        } else {
+            Symbol::new_func(ty.clone(), param_tys)
+        };
+        let symbol_id = symbol_table.set(name.clone(), symbol);
 
         Ok(DeclareFunction {
             ty,
             params,
             name,
             symbol_id,
+            span: self.span,
         })
     }
 }
@@ -67,35 +304,610 @@ impl ast::DeclareFunction {
 impl ast::Function {
     pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<Function, SemanticError> {
         let name = self.name.visit_common();
-        let ty = self.ty.visit_common();
+        let (ty_params, bounds) = visit_func_ty_params(self.ty_params, symbol_table);
 
-        let params = self
-            .params
-            .into_iter()
-            .map(|a| a.visit_ast(symbol_table))
-            .collect::<Result<Vec<_>, _>>()?;
+        // C calls public functions by their plain name, anything else might clash with another module
+        let link_name = match self.scope {
+            ast::Scope::Public => name.clone(),
+            ast::Scope::Shared | ast::Scope::Local => symbol_table.qualify(&name),
+        };
+
+        // C has no way to pick the type arguments, it's still declared so calls from Sonance check
+        if let (ast::Scope::Public, false) = (&self.scope, ty_params.is_empty()) {
+            symbol_table.report(
+                SemanticError::from(SemanticErrorKind::GenericCFunc { name: name.clone() })
+                    .at(self.span),
+            );
+        }
+
+        let (mut ty, mut params) = {
+            let symbol_table = &mut symbol_table.fork();
+            bind_ty_params(&ty_params, symbol_table);
+
+            let ty = self.ty.visit_common(symbol_table);
+            let params = self
+                .params
+                .into_iter()
+                .map(|a| a.visit_ast(symbol_table))
+                .collect::<Result<Vec<_>, _>>()?;
+            (ty, params)
+        };
+
+        // Generic signatures are laid out by each instance instead
+        if ty_params.is_empty() {
+            lay_out_signature_or_error(&mut params, &mut ty, self.span, symbol_table);
+        }
 
         let symbol_id = symbol_table.set(
             name.clone(),
-            Symbol::new_func(ty, params.iter().map(|p| p.ty).collect()),
+            Symbol::new_generic_func(
+                ty_params.clone(),
+                bounds.clone(),
+                ty.clone(),
+                params.iter().map(|p| p.ty.clone()).collect(),
+            ),
         );
 
+        // Anything else could be called from another module or from C
+        if let ast::Scope::Local = self.scope {
+            symbol_table.watch_uses(Lint::UnusedFunctions, symbol_id, name, self.span);
+        }
+
         Ok(Function {
             ty,
             params,
-            name,
+            name: link_name,
+            ty_params,
+            bounds,
+            scope: self.scope.visit_common(),
+            self_ty: None,
+            body: self.body,
+            symbol_id,
+            span: self.span,
+        })
+    }
+}
+
+impl ast::Impl {
+    pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<Vec<Function>, SemanticError> {
+        let ty_params = visit_ty_params(self.ty_params);
+
+        // The type with the impl's own parameters as its arguments
+        let self_ty = {
+            let symbol_table = &mut symbol_table.fork();
+            bind_ty_params(&ty_params, symbol_table);
+            self.ty.visit_common(symbol_table)
+        };
+
+        // Fields are written after a `.` too, so methods can't reuse their names
+        let (ty_name, fields) = match &self_ty {
+            Ty::Struct {
+                name, symbol_id, ..
+            } => {
+                let def = symbol_table
+                    .get_ty_def(*symbol_id)
+                    .expect("Type was laid out");
+                let fields = def.as_struct().expect("Symbol is a struct").fields.clone();
+                (name.clone(), fields)
+            }
+            Ty::Union { name, .. } => (name.clone(), Vec::new()),
+            ty if self.trait_.is_some() => (Identifier::new(ty.to_string()), Vec::new()),
+            _ => return Err(SemanticErrorKind::ImplOnNonAggregate { ty: self_ty }.into()),
+        };
+
+        let trait_ = match self.trait_ {
+            Some(name) => {
+                let (symbol_id, def) = lookup_trait(name.visit_common(), symbol_table)?;
+                if symbol_table.get_trait_impl(symbol_id, &self_ty).is_some() {
+                    return Err(SemanticErrorKind::DuplicateTraitImpl {
+                        trait_: def.name,
+                        ty: self_ty,
+                    }
+                    .into());
+                }
+
+                // Declared before its methods are checked, so another impl of the same trait
+                // is a duplicate even when this one has errors. The methods are filled in below
+                symbol_table.set_trait_impl(
+                    symbol_id,
+                    &self_ty,
+                    TraitImpl {
+                        self_ty: self_ty.clone(),
+                        methods: Vec::new(),
+                    },
+                );
+                Some((symbol_id, def))
+            }
+            None => None,
+        };
+
+        let trait_name = trait_.as_ref().map(|(_, def)| def.name.clone());
+        let mut functions = Vec::new();
+        let mut methods = Vec::new();
+        let mut failed = Vec::new();
+
+        // Each bad method is reported on its own. Those that clash with another, aren't part of
+        // the trait or don't take `self` are left out, the rest are still declared
+        for func in self.functions {
+            let span = func.span;
+            let method = func.name.clone().visit_common();
+
+            if symbol_table
+                .get_method(&self_ty, trait_name.as_ref(), &method)
+                .is_some()
+            {
+                symbol_table.report(
+                    SemanticError::from(SemanticErrorKind::DuplicateMethod {
+                        name: ty_name.clone(),
+                        method,
+                    })
+                    .at(span),
+                );
+                continue;
+            }
+
+            let expected = match &trait_ {
+                Some((_, def)) => match def.method(&method) {
+                    Some((_, expected)) => Some(expected.clone()),
+                    None => {
+                        symbol_table.report(
+                            SemanticError::from(SemanticErrorKind::ExtraTraitMethod {
+                                trait_: def.name.clone(),
+                                method,
+                            })
+                            .at(span),
+                        );
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            if fields.iter().any(|(field, _)| *field == method) {
+                symbol_table.report(
+                    SemanticError::from(SemanticErrorKind::MethodNameIsField {
+                        name: ty_name.clone(),
+                        method: method.clone(),
+                    })
+                    .at(span),
+                );
+            }
+
+            let checked = func.visit_method(
+                &self_ty,
+                &ty_name,
+                &ty_params,
+                trait_name.as_ref(),
+                symbol_table,
+            );
+            let (func, info) = match checked {
+                Ok(checked) => checked,
+                Err(error) => {
+                    symbol_table.report(error.at(span));
+                    failed.push(method);
+                    continue;
+                }
+            };
+
+            if let Some(expected) = expected {
+                if let Err(error) = check_trait_method(&func, &expected, &self_ty, ty_params.len())
+                {
+                    symbol_table.report(error.at(span));
+                }
+            }
+
+            symbol_table.set_method(&self_ty, trait_name.clone(), method.clone(), info.clone());
+            functions.push(func);
+            methods.push((method, info));
+        }
+
+        // Every method the trait asks for, in the order it asks for them
+        if let Some((symbol_id, def)) = trait_ {
+            let mut complete = true;
+            let mut ordered = Vec::with_capacity(def.methods.len());
+            for m in def.methods.iter() {
+                match methods.iter().find(|(name, _)| *name == m.name) {
+                    Some((_, info)) => ordered.push(info.clone()),
+                    None if failed.contains(&m.name) => complete = false,
+                    None => {
+                        symbol_table.report(
+                            SemanticError::from(SemanticErrorKind::MissingTraitMethod {
+                                trait_: def.name.clone(),
+                                ty: self_ty.clone(),
+                                method: m.name.clone(),
+                            })
+                            .at(self.span),
+                        );
+                        complete = false;
+                    }
+                }
+            }
+
+            // The methods it does have can still be called, and bounds on the trait are still
+            // met so they don't report it again, but it's never made into a vtable
+            if !complete {
+                return Ok(functions);
+            }
+            let methods = ordered;
+
+            symbol_table.set_trait_impl(
+                symbol_id,
+                &self_ty,
+                TraitImpl {
+                    self_ty: self_ty.clone(),
+                    methods,
+                },
+            );
+        }
+
+        Ok(functions)
+    }
+}
+
+impl ast::Function {
+    /// Like any other function, but named after its type and only reachable through it,
+    /// a trait method is named after its trait too, like `I32.geo::Shape.area`
+    fn visit_method(
+        self,
+        self_ty: &Ty,
+        ty_name: &Identifier,
+        impl_params: &[Identifier],
+        trait_: Option<&Identifier>,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<(Function, MethodInfo), SemanticError> {
+        let method = self.name.visit_common();
+        let link_name = Identifier::new(match trait_ {
+            Some(trait_) => format!(
+                "{}.{}.{}",
+                ty_name.as_string(),
+                trait_.as_string(),
+                method.as_string()
+            ),
+            None => format!("{}.{}", ty_name.as_string(), method.as_string()),
+        });
+
+        // Trait methods can be called wherever the trait can be used
+        let shared = match self.scope {
+            ast::Scope::Public => {
+                symbol_table.report(
+                    SemanticError::from(SemanticErrorKind::CMethod {
+                        method: link_name.clone(),
+                    })
+                    .at(self.span),
+                );
+                true
+            }
+            ast::Scope::Shared => true,
+            ast::Scope::Local => trait_.is_some(),
+        };
+
+        // The impl's parameters come first, so they line up with the receiver's type arguments
+        let (own_params, bounds) = visit_func_ty_params(self.ty_params, symbol_table);
+        let ty_params = impl_params
+            .iter()
+            .cloned()
+            .chain(own_params)
+            .collect::<Vec<_>>();
+
+        let (mut ty, mut params) = {
+            let symbol_table = &mut symbol_table.fork();
+            bind_ty_params(&ty_params, symbol_table);
+            symbol_table.set(
+                Identifier::new("Self".to_string()),
+                Symbol::new_ty(self_ty.clone()),
+            );
+
+            let ty = self.ty.visit_common(symbol_table);
+            let params = self
+                .params
+                .into_iter()
+                .map(|a| a.visit_ast(symbol_table))
+                .collect::<Result<Vec<_>, _>>()?;
+            (ty, params)
+        };
+
+        check_self(&params, self_ty, &link_name)?;
+
+        if ty_params.is_empty() {
+            lay_out_signature_or_error(&mut params, &mut ty, self.span, symbol_table);
+        }
+
+        let symbol = Symbol::new_generic_func(
+            ty_params.clone(),
+            bounds.clone(),
+            ty.clone(),
+            params.iter().map(|p| p.ty.clone()).collect(),
+        );
+        let symbol_id = symbol.id();
+
+        // Same as functions, only a private method can be known to be unused
+        if !shared {
+            symbol_table.watch_uses(
+                Lint::UnusedFunctions,
+                symbol_id,
+                link_name.clone(),
+                self.span,
+            );
+        }
+
+        let info = MethodInfo {
+            name: link_name.clone(),
+            symbol,
+            shared,
+            module: symbol_table.module().cloned(),
+        };
+
+        let func = Function {
+            ty,
+            params,
+            name: link_name,
+            ty_params,
+            bounds,
             scope: self.scope.visit_common(),
+            self_ty: Some(self_ty.clone()),
             body: self.body,
             symbol_id,
+            span: self.span,
+        };
+
+        Ok((func, info))
+    }
+}
+
+/// Signatures are checked with `Self` left as a type parameter, each impl fills it in
+fn visit_trait(
+    name: &Identifier,
+    methods: Vec<ast::DeclareFunction>,
+    symbol_table: &mut SymbolTable,
+) -> Result<(), SemanticError> {
+    let self_name = Identifier::new("Self".to_string());
+    let self_ty = Ty::Param(self_name.clone());
+    let trait_name = symbol_table
+        .get(name)
+        .and_then(Symbol::as_trait)
+        .expect("Trait was declared")
+        .name
+        .clone();
+
+    let mut checked = Vec::<TraitMethod>::new();
+    {
+        let symbol_table = &mut symbol_table.fork();
+        bind_ty_params(&[self_name], symbol_table);
+
+        for method in methods {
+            let span = method.span;
+            let method_name = method.name.visit_common();
+            if checked.iter().any(|m| m.name == method_name) {
+                return Err(SemanticError::from(SemanticErrorKind::DuplicateMethod {
+                    name: trait_name,
+                    method: method_name,
+                })
+                .at(span));
+            }
+
+            let ty = method.ty.visit_common(symbol_table);
+            let params = method
+                .params
+                .into_iter()
+                .map(|p| p.visit_ast(symbol_table))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let link_name = Identifier::new(format!(
+                "{}.{}",
+                trait_name.as_string(),
+                method_name.as_string()
+            ));
+            check_self(&params, &self_ty, &link_name).map_err(|error| error.at(span))?;
+
+            checked.push(TraitMethod {
+                name: method_name,
+                params: params.into_iter().map(|p| p.ty).collect(),
+                ty,
+            });
+        }
+    }
+
+    let symbol = symbol_table.get_mut(name).expect("Trait was declared");
+    symbol.as_trait_mut().expect("Symbol is a trait").methods = checked;
+
+    let symbol = symbol.clone();
+    symbol_table.set_ty_def(symbol);
+    Ok(())
+}
+
+/// Methods take `self` first, either the type itself or a pointer to it
+fn check_self(
+    params: &[Parameter],
+    self_ty: &Ty,
+    method: &Identifier,
+) -> Result<(), SemanticError> {
+    let by_pointer = |ty: &Ty| matches!(ty, Ty::Pointer { to, .. } if **to == *self_ty);
+    match params.first() {
+        Some(param)
+            if param.name.as_string() == "self"
+                && (param.ty == *self_ty || by_pointer(&param.ty)) =>
+        {
+            Ok(())
+        }
+        _ => Err(SemanticErrorKind::InvalidSelf {
+            method: method.clone(),
+        }
+        .into()),
+    }
+}
+
+/// An impl has to match the trait's signature exactly, once `Self` is its own type
+fn check_trait_method(
+    func: &Function,
+    expected: &TraitMethod,
+    self_ty: &Ty,
+    impl_params: usize,
+) -> Result<(), SemanticError> {
+    let substitution = Some((Identifier::new("Self".to_string()), self_ty.clone()))
+        .into_iter()
+        .collect::<Substitution>();
+
+    let expected = Ty::Func {
+        params: expected
+            .params
+            .iter()
+            .map(|p| p.substitute(&substitution))
+            .collect(),
+        ret: Box::new(expected.ty.substitute(&substitution)),
+    };
+    let found = Ty::Func {
+        params: func.params.iter().map(|p| p.ty.clone()).collect(),
+        ret: Box::new(func.ty.clone()),
+    };
+
+    // A vtable has one address per method, so there's no room for more type parameters
+    if expected != found || func.ty_params.len() != impl_params {
+        return Err(SemanticErrorKind::TraitMethodMismatch { expected, found }.into());
+    }
+    Ok(())
+}
+
+fn lookup_trait(
+    name: Identifier,
+    symbol_table: &SymbolTable,
+) -> Result<(SymbolId, TraitDef), SemanticError> {
+    let symbol = symbol_table
+        .get(&name)
+        .ok_or_else(|| SemanticErrorKind::TraitNotFound {
+            symbol: name.clone(),
+        })?;
+    let def = symbol
+        .as_trait()
+        .ok_or(SemanticErrorKind::ExpectedTraitSymbol { symbol: name })?;
+    Ok((symbol.id(), def.clone()))
+}
+
+/// Names of a function's type parameters, and the traits their arguments need impls of.
+/// A bound that isn't a trait is reported and left out
+fn visit_func_ty_params(
+    ty_params: Vec<ast::TyParam>,
+    symbol_table: &SymbolTable,
+) -> (Vec<Identifier>, Vec<Bound>) {
+    let mut names = Vec::with_capacity(ty_params.len());
+    let mut bounds = Vec::new();
+
+    for param in ty_params {
+        let name = param.name.visit_common();
+        let span = param.span;
+        for bound in param.bounds {
+            let (symbol_id, def) = match lookup_trait(bound.visit_common(), symbol_table) {
+                Ok(found) => found,
+                Err(error) => {
+                    symbol_table.report(error.at(span));
+                    continue;
+                }
+            };
+            bounds.push(Bound {
+                param: name.clone(),
+                name: def.name,
+                symbol_id,
+            });
+        }
+        names.push(name);
+    }
+
+    (names, bounds)
+}
+
+impl ast::Const {
+    pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<Const, SemanticError> {
+        let ty = self.ty.visit_common(symbol_table);
+        let symbol_id = symbol_table.set(self.name.visit_common(), Symbol::new_const(ty.clone()));
+
+        Ok(Const {
+            ty,
+            value: self.value,
+            symbol_id,
+            span: self.span,
+        })
+    }
+}
+
+impl ast::Static {
+    pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<Static, SemanticError> {
+        let name = self.name.visit_common();
+        let mut ty = self.ty.visit_common(symbol_table);
+        lay_out_or_error(&mut ty, self.span, symbol_table);
+
+        // Same as functions, only public "c" statics keep their plain name
+        let link_name = match self.scope {
+            ast::Scope::Public => name.clone(),
+            ast::Scope::Shared | ast::Scope::Local => symbol_table.qualify(&name),
+        };
+
+        let symbol_id = symbol_table.set(name, Symbol::new_static(ty.clone(), self.mutable));
+
+        Ok(Static {
+            scope: self.scope.visit_common(),
+            mutable: self.mutable,
+            name: link_name,
+            ty,
+            value: self.value,
+            symbol_id,
+            span: self.span,
         })
     }
 }
 
 impl ast::Parameter {
-    pub fn visit_ast(self, _: &mut SymbolTable) -> Result<Parameter, SemanticError> {
+    pub fn visit_ast(self, symbol_table: &mut SymbolTable) -> Result<Parameter, SemanticError> {
         Ok(Parameter {
+            mutable: self.mutable,
             name: self.name.visit_common(),
-            ty: self.ty.visit_common(),
+            ty: self.ty.visit_common(symbol_table),
+            span: self.span,
         })
     }
 }
+
+fn visit_ty_params(ty_params: Vec<ast::Identifier>) -> Vec<Identifier> {
+    ty_params
+        .into_iter()
+        .map(ast::Identifier::visit_common)
+        .collect()
+}
+
+/// Inside a generic item its type parameters stand for types that aren't known yet
+fn bind_ty_params(ty_params: &[Identifier], symbol_table: &mut SymbolTable) {
+    for param in ty_params {
+        symbol_table.set(param.clone(), Symbol::new_ty(Ty::Param(param.clone())));
+    }
+}
+
+/// The backend needs to know the size of anything passed in or out
+pub fn lay_out_signature(
+    params: &[Parameter],
+    ty: &Ty,
+    symbol_table: &SymbolTable,
+) -> Result<(), SemanticError> {
+    for ty in params.iter().map(|p| &p.ty).chain(Some(ty)) {
+        symbol_table.ty_layout(ty)?;
+    }
+    Ok(())
+}
+
+/// Like `lay_out_signature`, but a type that can't be laid out is reported and becomes `Ty::Error`,
+/// so the function is still declared
+fn lay_out_signature_or_error(
+    params: &mut [Parameter],
+    ty: &mut Ty,
+    span: Span,
+    symbol_table: &SymbolTable,
+) {
+    for param in params {
+        lay_out_or_error(&mut param.ty, param.span, symbol_table);
+    }
+    lay_out_or_error(ty, span, symbol_table);
+}
+
+fn lay_out_or_error(ty: &mut Ty, span: Span, symbol_table: &SymbolTable) {
+    if let Err(error) = symbol_table.ty_layout(ty) {
+        symbol_table.report(error.at(span));
+        *ty = Ty::Error;
+    }
+}
@@ -3,17 +3,47 @@ use super::super::*;
 #[derive(Debug, Clone)]
 pub struct File {
     pub items: Vec<Item>,
+    /// Names of the items other modules can import
+    pub exports: Vec<Identifier>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Item {
     Declare(DeclareBlock),
     Function(Function),
+    Struct(Struct),
+    Union(Union),
+    Const(Const),
+    Static(Static),
+}
+
+impl Item {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Declare(item) => item.span,
+            Self::Function(item) => item.span,
+            Self::Struct(item) => item.span,
+            Self::Union(item) => item.span,
+            Self::Const(item) => item.span,
+            Self::Static(item) => item.span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DeclareBlock {
     pub functions: Vec<DeclareFunction>,
+    pub statics: Vec<DeclareStatic>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeclareStatic {
+    pub mutable: bool,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub symbol_id: SymbolId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -22,20 +52,67 @@ pub struct DeclareFunction {
     pub params: Vec<Parameter>,
     pub ty: Ty,
     pub symbol_id: SymbolId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub scope: Scope,
     pub name: Identifier,
+    /// Generic functions are only checked once they're called with some arguments
+    pub ty_params: Vec<Identifier>,
+    /// Each instance can call the methods of these traits on its type arguments
+    pub bounds: Vec<Bound>,
     pub params: Vec<Parameter>,
     pub ty: Ty,
+    /// What `Self` means in the body of a method
+    pub self_ty: Option<Ty>,
     pub body: ast::Block,
     pub symbol_id: SymbolId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub ty: Ty,
+    pub value: ast::Expression,
+    pub symbol_id: SymbolId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub scope: Scope,
+    pub mutable: bool,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub value: ast::Expression,
+    pub symbol_id: SymbolId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name: Identifier,
+    pub fields: Vec<FieldInfo>,
+    pub layout: Layout,
+    pub symbol_id: SymbolId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Union {
+    pub name: Identifier,
+    pub variants: Vec<VariantInfo>,
+    pub layout: Layout,
+    pub symbol_id: SymbolId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Parameter {
+    pub mutable: bool,
     pub name: Identifier,
     pub ty: Ty,
+    pub span: Span,
 }
@@ -0,0 +1,172 @@
+use {
+    super::*,
+    std::{fmt, str::FromStr},
+    thiserror::Error,
+};
+
+/// A kind of warning, its name is how `#[allow(...)]` and the command line pick it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedMut,
+    UnusedVariables,
+    UnusedFunctions,
+    UnreachableCode,
+    Shadowing,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedMut,
+        Lint::UnusedVariables,
+        Lint::UnusedFunctions,
+        Lint::UnreachableCode,
+        Lint::Shadowing,
+    ];
+
+    /// These are part of the command line and of source files, so they never change
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedMut => "unused_mut",
+            Lint::UnusedVariables => "unused_variables",
+            Lint::UnusedFunctions => "unused_functions",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::Shadowing => "shadowing",
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .iter()
+            .copied()
+            .find(|lint| lint.name() == name)
+            .ok_or_else(|| format!("Unknown lint `{}`", name))
+    }
+}
+
+/// What happens to a lint's warnings, any lint not given one warns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    /// Stop the compile, like an error would
+    Deny,
+}
+
+/// Doesn't stop the program compiling, but is likely a mistake
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SemanticWarning {
+    #[error("Local {symbol:?} is declared `mut` but never changed")]
+    UnusedMut {
+        symbol: type_check::Identifier,
+        span: Span,
+    },
+    #[error("Local {symbol:?} is never used")]
+    UnusedVariable {
+        symbol: type_check::Identifier,
+        span: Span,
+    },
+    #[error("Function {symbol:?} is never used")]
+    UnusedFunction {
+        symbol: type_check::Identifier,
+        span: Span,
+    },
+    #[error("Unreachable code, everything before it jumps away")]
+    UnreachableCode { span: Span },
+    #[error("Local {symbol:?} shadows one declared earlier in the same block")]
+    Shadowing {
+        symbol: type_check::Identifier,
+        span: Span,
+    },
+}
+
+impl SemanticWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::UnusedMut { span, .. }
+            | Self::UnusedVariable { span, .. }
+            | Self::UnusedFunction { span, .. }
+            | Self::UnreachableCode { span }
+            | Self::Shadowing { span, .. } => *span,
+        }
+    }
+
+    pub fn lint(&self) -> Lint {
+        match self {
+            Self::UnusedMut { .. } => Lint::UnusedMut,
+            Self::UnusedVariable { .. } => Lint::UnusedVariables,
+            Self::UnusedFunction { .. } => Lint::UnusedFunctions,
+            Self::UnreachableCode { .. } => Lint::UnreachableCode,
+            Self::Shadowing { .. } => Lint::Shadowing,
+        }
+    }
+}
+
+impl ast::Attribute {
+    /// `#[allow(lint, ...)]` is the only attribute so far
+    pub fn visit_ast(self, symbol_table: &SymbolTable) -> Result<(), SemanticError> {
+        let name = self.name.visit_common();
+        if name.as_string() != "allow" {
+            return Err(SemanticErrorKind::UnknownAttribute { name }.into());
+        }
+
+        for arg in self.args {
+            let arg = arg.visit_common();
+            let lint = arg
+                .as_string()
+                .parse()
+                .map_err(|_| SemanticErrorKind::UnknownLint { name: arg.clone() })?;
+            symbol_table.allow(lint, self.target);
+        }
+        Ok(())
+    }
+}
+
+/// Every warning found while checking, leaving out those that are allowed.
+/// Each instance of a generic function warns about the same code, but it's only shown once
+pub fn visit_warnings(
+    root: &mut SymbolTable,
+    levels: &HashMap<Lint, LintLevel>,
+) -> Vec<SemanticWarning> {
+    let mut warnings = root.take_warnings();
+    warnings.extend(
+        root.take_unmutated()
+            .into_iter()
+            .map(|(symbol, span)| SemanticWarning::UnusedMut { symbol, span }),
+    );
+    warnings.extend(
+        root.take_unused()
+            .into_iter()
+            .map(|(lint, symbol, span)| match lint {
+                Lint::UnusedFunctions => SemanticWarning::UnusedFunction { symbol, span },
+                _ => SemanticWarning::UnusedVariable { symbol, span },
+            }),
+    );
+
+    let allowed = root.take_allowed();
+    warnings.retain(|warning| {
+        let lint = warning.lint();
+        let span = warning.span();
+        levels.get(&lint) != Some(&LintLevel::Allow)
+            && !allowed
+                .iter()
+                .any(|(allowed, within)| *allowed == lint && contains(*within, span))
+    });
+
+    warnings.sort_by_key(|warning| (warning.span(), warning.lint().name()));
+    warnings.dedup();
+    warnings
+}
+
+fn contains(outer: Span, inner: Span) -> bool {
+    outer.file == inner.file && outer.start <= inner.start && inner.end <= outer.end
+}
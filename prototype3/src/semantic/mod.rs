@@ -1,23 +1,114 @@
 pub mod common;
 pub mod header;
+pub mod lint;
 pub mod symbol_table;
 pub mod type_check;
 
-pub use {common::*, symbol_table::*};
+pub use {crate::diagnostic::Span, common::*, lint::*, symbol_table::*};
 
-use super::ast;
+use {
+    super::{ast, CompileOptions},
+    std::collections::{HashMap, HashSet},
+};
 
-pub fn semantic_pass(input: ast::File) -> Result<type_check::File, SemanticError> {
-    let mut symbol_table = SymbolTable::new();
-    input
-        .visit_ast(&mut symbol_table)?
-        .visit_header(&mut symbol_table)
+/// Modules are checked in order, each one can only import those before it.
+/// Checking goes on past an error, so every one that doesn't follow from another is returned
+pub fn semantic_pass(
+    input: Vec<ast::File>,
+    options: &CompileOptions,
+) -> Result<type_check::File, Vec<SemanticError>> {
+    let mut root = SymbolTable::new().with_legacy_truthiness(options.legacy_truthiness);
+    let prelude = header::visit_prelude(&mut root)
+        .into_iter()
+        .map(|item| type_check::Item::Struct(item.visit_header()))
+        .collect::<Vec<_>>();
+
+    let mut items = {
+        let mut modules = HashMap::new();
+        let mut symbol_tables = Vec::with_capacity(input.len());
+        let mut templates = HashMap::new();
+        let mut items = Vec::new();
+
+        for file in input {
+            let module = file.module.clone().map(ast::Identifier::visit_common);
+            let mut symbol_table = root.fork().in_module(module.clone());
+
+            let header = file.visit_ast(&modules, &mut symbol_table);
+            if let Some(module) = module {
+                modules.insert(module, symbol_table.module_info(&header.exports));
+            }
+
+            let (checked, generic) = header.visit_header(&mut symbol_table);
+            items.extend(checked);
+            templates.extend(
+                generic
+                    .into_iter()
+                    .map(|func| (func.symbol_id, (symbol_tables.len(), func))),
+            );
+            symbol_tables.push(symbol_table);
+        }
+
+        items.extend(type_check::monomorphize::visit_instances(
+            &templates,
+            &mut symbol_tables,
+        ));
+        items
+    };
+
+    let errors = root.take_errors();
+    if !errors.is_empty() {
+        return Err(in_order(errors));
+    }
+
+    items.extend(type_check::monomorphize::visit_ty_instances(&mut root));
+    items.extend(prelude);
+
+    Ok(type_check::File {
+        items,
+        address_taken: root.take_address_taken(),
+        warnings: lint::visit_warnings(&mut root, &options.lint_levels),
+    })
+}
+
+/// Errors by where they are in the source, each instance of a generic function
+/// can find the same one in its body but it's only shown once
+fn in_order(mut errors: Vec<SemanticError>) -> Vec<SemanticError> {
+    errors.sort_by_key(|error| error.span);
+
+    let mut seen = HashSet::new();
+    errors.retain(|error| seen.insert((error.span, error.kind.to_string())));
+    errors
 }
 
 use thiserror::Error;
 
+/// What went wrong and where, the innermost node that fails gives the span
 #[derive(Debug, Clone, Error)]
-pub enum SemanticError {
+#[error("{kind}")]
+pub struct SemanticError {
+    pub kind: Box<SemanticErrorKind>,
+    pub span: Option<Span>,
+}
+
+impl SemanticError {
+    /// Point the error at `span`, unless a node inside it already has
+    pub fn at(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+}
+
+impl From<SemanticErrorKind> for SemanticError {
+    fn from(kind: SemanticErrorKind) -> Self {
+        SemanticError {
+            kind: Box::new(kind),
+            span: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum SemanticErrorKind {
     #[error(
         "Type Mismatch: Function return declared as {expected:?} but found an return of {found:?}"
     )]
@@ -32,6 +123,13 @@ pub enum SemanticError {
         expected: type_check::Ty,
         found: type_check::Ty,
     },
+    #[error(
+        "Type Mismatch: Const or static declared as {expected:?} but found an initializer of {found:?}"
+    )]
+    TyMismatchInitializer {
+        expected: type_check::Ty,
+        found: type_check::Ty,
+    },
     #[error("Type Mismatch: Variable declared previously as {expected:?} but trying to assign with {found:?}")]
     TyMismatchAssign {
         expected: type_check::Ty,
@@ -43,6 +141,51 @@ pub enum SemanticError {
         right: type_check::Ty,
         operator: type_check::InfixOperator,
     },
+    #[error("Type Mismatch: Prefix {operator:?} can't be used on {value:?}")]
+    TyMismatchPrefix {
+        value: type_check::Ty,
+        operator: type_check::PrefixOperator,
+    },
+    #[error("Type Mismatch: Operator `{operator}` only works on integers, but found {ty:?}")]
+    IntegerOnlyOperator {
+        operator: &'static str,
+        ty: type_check::Ty,
+    },
+    #[error("Type Mismatch: Predicate must be a Bool but found {found:?}")]
+    TyMismatchPredicate { found: type_check::Ty },
+    #[error("Type Mismatch: If expression of {when_true:?} has no default, it needs an else")]
+    MissingElse { when_true: type_check::Ty },
+    #[error("Type Mismatch: Can't dereference {found:?}, it isn't a pointer")]
+    DerefNonPointer { found: type_check::Ty },
+    #[error("Type Mismatch: Can't call {found:?}, it isn't a function")]
+    CallNonFunc { found: type_check::Ty },
+    #[error("Type Mismatch: Can't cast {from:?} to {to:?}, only numbers can be cast")]
+    InvalidCast {
+        from: type_check::Ty,
+        to: type_check::Ty,
+    },
+    #[error("Can't assign through {found:?}, it isn't a `*mut` pointer")]
+    AssignThroughConstPointer { found: type_check::Ty },
+    #[error("Can't assign through {found:?}, it isn't a `[mut T]` slice")]
+    AssignThroughConstSlice { found: type_check::Ty },
+    #[error("Type Mismatch: Can't index {found:?}, it isn't an array or slice")]
+    IndexNonArray { found: type_check::Ty },
+    #[error("Type Mismatch: Index must be an integer but found {found:?}")]
+    TyMismatchIndex { found: type_check::Ty },
+    #[error("Index {index} is out of bounds for an array of {len}")]
+    IndexOutOfBounds { index: i128, len: u32 },
+    #[error(
+        "Type Mismatch: Array starts with {expected:?} but found {found:?} in position {position}"
+    )]
+    TyMismatchArrayElement {
+        expected: type_check::Ty,
+        found: type_check::Ty,
+        position: usize,
+    },
+    #[error("Can't tell what an empty array holds, give the let binding a type")]
+    CantInferArrayTy,
+    #[error("Type {ty:?} is too large, it doesn't fit in 4 GiB")]
+    ArrayTooLarge { ty: type_check::Ty },
     #[error("Type Mismatch: If expression has two incompatible results ({when_true:?} and {when_false:?})")]
     TyMismatchIfElse {
         when_true: type_check::Ty,
@@ -54,6 +197,135 @@ pub enum SemanticError {
         found: type_check::Ty,
         position: usize,
     },
+    #[error("Type Mismatch: Loop previously broken out of with {expected:?} but found a break with {found:?}")]
+    TyMismatchBreak {
+        expected: type_check::Ty,
+        found: type_check::Ty,
+    },
+    #[error("Type Mismatch: Can't access field {field:?} on {found:?}, it isn't a struct")]
+    FieldOnNonStruct {
+        found: type_check::Ty,
+        field: type_check::Identifier,
+    },
+    #[error("Struct {name:?} has no field {field:?}")]
+    FieldNotFound {
+        name: type_check::Identifier,
+        field: type_check::Identifier,
+    },
+    #[error("Struct {name:?} has field {field:?} more than once")]
+    DuplicateField {
+        name: type_check::Identifier,
+        field: type_check::Identifier,
+    },
+    #[error("Struct literal of {name:?} is missing field {field:?}")]
+    MissingField {
+        name: type_check::Identifier,
+        field: type_check::Identifier,
+    },
+    #[error("Type {name:?} takes {expected} type arguments but found {found}")]
+    WrongTyArgCount {
+        name: type_check::Identifier,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Can't infer type parameter {param:?} of {name:?}, give the let binding a type")]
+    CantInferTyParam {
+        param: type_check::Identifier,
+        name: type_check::Identifier,
+    },
+    #[error("Function {name:?} keeps calling itself with bigger type arguments")]
+    InstantiationTooDeep { name: type_check::Identifier },
+    #[error("Function {name:?} can't be both generic and public, C has no way to give it types")]
+    GenericCFunc { name: type_check::Identifier },
+    #[error("Can't impl {ty:?} on its own, only structs and unions have their own methods")]
+    ImplOnNonAggregate { ty: type_check::Ty },
+    #[error("Method {method:?} needs `self: Self`, `self: *Self` or `self: *mut Self` as its first parameter")]
+    InvalidSelf { method: type_check::Identifier },
+    #[error("Method {method:?} can't be public \"c\", C has no way to name it")]
+    CMethod { method: type_check::Identifier },
+    #[error("Type {name:?} has method {method:?} more than once")]
+    DuplicateMethod {
+        name: type_check::Identifier,
+        method: type_check::Identifier,
+    },
+    #[error("Type {name:?} already has a field {method:?}, a method can't share its name")]
+    MethodNameIsField {
+        name: type_check::Identifier,
+        method: type_check::Identifier,
+    },
+    #[error("Type {found:?} has no method or function field {method:?}")]
+    MethodNotFound {
+        found: type_check::Ty,
+        method: type_check::Identifier,
+    },
+    #[error("Method {method:?} of {ty:?} comes from more than one trait: {traits:?}, call it from a function generic over the one you mean")]
+    AmbiguousMethod {
+        method: type_check::Identifier,
+        ty: type_check::Ty,
+        traits: Vec<type_check::Identifier>,
+    },
+    #[error("Can't call method {method:?}, it isn't public")]
+    PrivateMethod { method: type_check::Identifier },
+    #[error("Method {method:?} takes `self` by pointer, call it on a local or through a pointer")]
+    ReceiverNotPlace { method: type_check::Identifier },
+    #[error("Trait {symbol:?} not found in the current scope")]
+    TraitNotFound { symbol: type_check::Identifier },
+    #[error("Expected symbol {symbol:?} to be a trait, it wasn't")]
+    ExpectedTraitSymbol { symbol: type_check::Identifier },
+    #[error("Trait {trait_:?} is already implemented for {ty:?}")]
+    DuplicateTraitImpl {
+        trait_: type_check::Identifier,
+        ty: type_check::Ty,
+    },
+    #[error("Impl of trait {trait_:?} for {ty:?} is missing method {method:?}")]
+    MissingTraitMethod {
+        trait_: type_check::Identifier,
+        ty: type_check::Ty,
+        method: type_check::Identifier,
+    },
+    #[error("Method {method:?} isn't part of trait {trait_:?}")]
+    ExtraTraitMethod {
+        trait_: type_check::Identifier,
+        method: type_check::Identifier,
+    },
+    #[error("Type Mismatch: Trait method declared as {expected:?} but implemented as {found:?}")]
+    TraitMethodMismatch {
+        expected: type_check::Ty,
+        found: type_check::Ty,
+    },
+    #[error("Type {ty:?} doesn't implement trait {trait_:?}")]
+    TraitNotImplemented {
+        trait_: type_check::Identifier,
+        ty: type_check::Ty,
+    },
+    #[error("Can't use trait {trait_:?} as `dyn`, method {method:?} needs `self` behind a pointer and `Self` nowhere else")]
+    TraitNotObjectSafe {
+        trait_: type_check::Identifier,
+        method: type_check::Identifier,
+    },
+    #[error("Method {method:?} takes `*mut Self`, but {found:?} isn't `dyn mut`")]
+    MutMethodOnConstDyn {
+        method: type_check::Identifier,
+        found: type_check::Ty,
+    },
+    #[error("Nothing called {path:?} was found to import")]
+    ImportNotFound { path: type_check::Identifier },
+    #[error("Can't import {path:?}, it isn't public")]
+    PrivateImport { path: type_check::Identifier },
+    #[error("Type {name:?} contains itself, use a pointer to break the cycle")]
+    RecursiveStruct { name: type_check::Identifier },
+    #[error("Const and static initializers can only use literals, consts, operators and casts, statics can also group those into arrays and structs")]
+    NotConstant,
+    #[error("Const {symbol:?} is used before its value is known, declare it before the consts that use it")]
+    ConstUsedBeforeValue { symbol: type_check::Identifier },
+    #[error("Division by zero while working out a const")]
+    ConstDivideByZero,
+    #[error("Value doesn't fit in {ty:?}, `as!` would trap")]
+    ConstCastOutOfRange { ty: type_check::Ty },
+    #[error("Static {symbol:?} can't be changed, it isn't `static mut`")]
+    ImmutableStatic { symbol: type_check::Identifier },
+    #[error("Local {symbol:?} can't be changed, it isn't declared `mut`")]
+    ImmutableLocal { symbol: type_check::Identifier },
     #[error("Local {symbol:?} not found in the current scope")]
     LocalNotFound { symbol: type_check::Identifier },
     #[error("Expected symbol {symbol:?} to be a local, it wasn't")]
@@ -62,8 +334,121 @@ pub enum SemanticError {
     FuncNotFound { symbol: type_check::Identifier },
     #[error("Expected symbol {symbol:?} to be a function, it wasn't")]
     ExpectedFuncSymbol { symbol: type_check::Identifier },
+    #[error("Type {symbol:?} not found in the current scope")]
+    TyNotFound { symbol: type_check::Identifier },
+    #[error("Expected symbol {symbol:?} to be a struct, it wasn't")]
+    ExpectedStructSymbol { symbol: type_check::Identifier },
+    #[error("Expected symbol {symbol:?} to be a type, it wasn't")]
+    ExpectedTySymbol { symbol: type_check::Identifier },
+    #[error("Variant {symbol:?} not found in the current scope")]
+    VariantNotFound { symbol: type_check::Identifier },
+    #[error("Expected symbol {symbol:?} to be a union variant, it wasn't")]
+    ExpectedVariantSymbol { symbol: type_check::Identifier },
+    #[error("Variant {variant:?} is already defined by another union")]
+    DuplicateVariant { variant: type_check::Identifier },
+    #[error("Variant {variant:?} takes {expected} values but found {found}")]
+    WrongPayloadCount {
+        variant: type_check::Identifier,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Type Mismatch: Can't match {found:?} against variant {variant:?}")]
+    TyMismatchPattern {
+        found: type_check::Ty,
+        variant: type_check::Identifier,
+    },
+    #[error("Type Mismatch: Match arm gives {found:?} but an earlier arm gave {expected:?}")]
+    TyMismatchMatch {
+        expected: type_check::Ty,
+        found: type_check::Ty,
+    },
+    #[error("Match arm {position} can never run, earlier arms already cover it")]
+    /// Counted from 1, the way the arms read
+    UnreachableArm { position: usize },
+    #[error("Match doesn't cover every variant, missing {missing:?}")]
+    NonExhaustiveMatch {
+        missing: Vec<type_check::Identifier>,
+    },
+    #[error("Label {label:?} not found on any enclosing loop")]
+    LabelNotFound { label: type_check::Identifier },
+    #[error("Can't use `{keyword}` outside of a loop")]
+    JumpOutsideLoop { keyword: &'static str },
+    #[error("Can't break with a value out of a `while` loop")]
+    BreakValueInWhile,
     #[error("Not Enough Arguments (expected {expected}, found {found})")]
     NotEnoughArgs { expected: usize, found: usize },
     #[error("Too Many Arguments (expected {expected}, found {found})")]
     TooManyArgs { expected: usize, found: usize },
+    #[error(
+        "Can't pass {found:?} as variadic argument {position}, only numbers, bools and pointers"
+    )]
+    InvalidVariadicArg {
+        found: type_check::Ty,
+        position: usize,
+    },
+    #[error("Function {name:?} is variadic, it can only be called directly")]
+    VariadicFuncValue { name: type_check::Identifier },
+    #[error(
+        "Can only assign to or take the address of a local, static, field, element or `*pointer`"
+    )]
+    NotPlace,
+    #[error("Unknown attribute {name:?}, only `allow` is supported")]
+    UnknownAttribute { name: type_check::Identifier },
+    #[error("Unknown lint {name:?}")]
+    UnknownLint { name: type_check::Identifier },
+    /// Stops checking something that can't go on without what failed, and is never shown
+    #[error("An error this follows from was already reported")]
+    AlreadyReported,
+}
+
+impl SemanticErrorKind {
+    /// Whether it only follows from an earlier error, which gave something `Ty::Error`
+    pub fn is_cascade(&self) -> bool {
+        use SemanticErrorKind::*;
+        let tys = match self {
+            AlreadyReported => return true,
+            TyMismatchReturn { expected, found }
+            | TyMismatchDeclare { expected, found }
+            | TyMismatchInitializer { expected, found }
+            | TyMismatchAssign { expected, found }
+            | TyMismatchArrayElement {
+                expected, found, ..
+            }
+            | TyMismatchArg {
+                expected, found, ..
+            }
+            | TyMismatchBreak { expected, found }
+            | TraitMethodMismatch { expected, found }
+            | TyMismatchMatch { expected, found } => vec![expected, found],
+            TyMismatchOperator { left, right, .. } => vec![left, right],
+            TyMismatchIfElse {
+                when_true,
+                when_false,
+            } => vec![when_true, when_false],
+            InvalidCast { from, to } => vec![from, to],
+            TyMismatchPrefix { value, .. } => vec![value],
+            MissingElse { when_true } => vec![when_true],
+            TyMismatchPredicate { found }
+            | DerefNonPointer { found }
+            | CallNonFunc { found }
+            | AssignThroughConstPointer { found }
+            | AssignThroughConstSlice { found }
+            | IndexNonArray { found }
+            | TyMismatchIndex { found }
+            | FieldOnNonStruct { found, .. }
+            | MethodNotFound { found, .. }
+            | MutMethodOnConstDyn { found, .. }
+            | TyMismatchPattern { found, .. }
+            | InvalidVariadicArg { found, .. } => vec![found],
+            IntegerOnlyOperator { ty, .. }
+            | ArrayTooLarge { ty }
+            | ImplOnNonAggregate { ty }
+            | DuplicateTraitImpl { ty, .. }
+            | MissingTraitMethod { ty, .. }
+            | TraitNotImplemented { ty, .. }
+            | ConstCastOutOfRange { ty } => vec![ty],
+            _ => Vec::new(),
+        };
+        tys.into_iter().any(Ty::has_error)
+    }
 }
@@ -1,12 +1,55 @@
 use {
-    super::{Identifier, Ty},
-    std::collections::HashMap,
+    super::{
+        type_check::Literal, Identifier, Layout, Lint, SemanticError, SemanticWarning, Span, Ty,
+    },
+    std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+        rc::Rc,
+    },
 };
 
 #[derive(Debug, Clone, Default)]
 pub struct SymbolTable<'a> {
     symbols: HashMap<Identifier, Symbol>,
     parent: Option<&'a SymbolTable<'a>>,
+    legacy_truthiness: bool,
+    /// Path of the module being checked, `None` for the entry file unless it declares one
+    module: Option<Identifier>,
+    /// What `return` has to give back, set for the body of each function
+    return_ty: Option<Ty>,
+    /// The function whose body is being checked, calling itself doesn't count as a use
+    function: Option<SymbolId>,
+    /// Traits the instance being checked was bound by, with the type argument each one applies to
+    bounds: Vec<(Ty, Identifier)>,
+    /// Set while checking an arg that's checked again once the other args are known,
+    /// so type arguments that can't be inferred come back as errors instead of being reported
+    can_retry: bool,
+    address_taken: RefCell<HashSet<SymbolId>>,
+    /// Every error found so far, checking carries on past them to find the rest
+    errors: RefCell<Vec<SemanticError>>,
+    /// Warnings found as they're checked, those about what's never used come at the end
+    warnings: RefCell<Vec<SemanticWarning>>,
+    /// Where `#[allow(...)]` turns off each lint
+    allowed: RefCell<Vec<(Lint, Span)>>,
+    /// Locals and functions to warn about if nothing uses them
+    watched: RefCell<Vec<(Lint, SymbolId, Identifier, Span)>>,
+    uses: RefCell<HashMap<SymbolId, u32>>,
+    /// Every `mut` local by name, to point out those nothing changes
+    mutable_locals: RefCell<Vec<(SymbolId, Identifier, Span)>>,
+    mutated: RefCell<HashSet<SymbolId>>,
+    /// Struct, union and trait definitions by id, so they can be found from any module
+    ty_defs: RefCell<HashMap<SymbolId, Symbol>>,
+    /// Values of consts by id, known once their item is checked, `None` if their initializer failed
+    const_values: RefCell<HashMap<SymbolId, Option<Literal>>>,
+    /// Every aggregate laid out so far, generic instances included
+    laid_out: RefCell<HashMap<Ty, TyInfo>>,
+    /// Methods from every `impl`, by the type they belong to, see `method_key`.
+    /// Those from a trait impl are also keyed by the trait, so two traits can share a method name
+    methods: RefCell<HashMap<Ty, HashMap<MethodName, MethodInfo>>>,
+    /// Impls of each trait, by the trait's id and the type
+    trait_impls: RefCell<HashMap<(SymbolId, Ty), TraitImpl>>,
+    func_instances: RefCell<FuncInstances>,
 }
 
 impl<'a> SymbolTable<'a> {
@@ -14,14 +57,369 @@ impl<'a> SymbolTable<'a> {
         SymbolTable {
             symbols: HashMap::new(),
             parent: None,
+            legacy_truthiness: false,
+            module: None,
+            return_ty: None,
+            function: None,
+            bounds: Vec::new(),
+            can_retry: false,
+            address_taken: RefCell::default(),
+            errors: RefCell::default(),
+            warnings: RefCell::default(),
+            allowed: RefCell::default(),
+            watched: RefCell::default(),
+            uses: RefCell::default(),
+            mutable_locals: RefCell::default(),
+            mutated: RefCell::default(),
+            ty_defs: RefCell::default(),
+            const_values: RefCell::default(),
+            laid_out: RefCell::default(),
+            methods: RefCell::default(),
+            trait_impls: RefCell::default(),
+            func_instances: RefCell::default(),
         }
     }
 
+    /// Let `if` and `while` accept any integer, treating `0` as false
+    pub fn with_legacy_truthiness(mut self, legacy_truthiness: bool) -> Self {
+        self.legacy_truthiness = legacy_truthiness;
+        self
+    }
+
     pub fn fork(&'a self) -> Self {
         SymbolTable {
             symbols: HashMap::new(),
             parent: Some(self),
+            legacy_truthiness: self.legacy_truthiness,
+            module: self.module.clone(),
+            return_ty: self.return_ty.clone(),
+            function: self.function,
+            bounds: Vec::new(),
+            can_retry: self.can_retry,
+            address_taken: RefCell::default(),
+            errors: RefCell::default(),
+            warnings: RefCell::default(),
+            allowed: RefCell::default(),
+            watched: RefCell::default(),
+            uses: RefCell::default(),
+            mutable_locals: RefCell::default(),
+            mutated: RefCell::default(),
+            ty_defs: RefCell::default(),
+            const_values: RefCell::default(),
+            laid_out: RefCell::default(),
+            methods: RefCell::default(),
+            trait_impls: RefCell::default(),
+            func_instances: RefCell::default(),
+        }
+    }
+
+    pub fn in_module(mut self, module: Option<Identifier>) -> Self {
+        self.module = module;
+        self
+    }
+
+    pub fn set_return_ty(&mut self, ty: Ty) {
+        self.return_ty = Some(ty);
+    }
+
+    pub fn return_ty(&self) -> Option<&Ty> {
+        self.return_ty.as_ref()
+    }
+
+    pub fn set_function(&mut self, id: SymbolId) {
+        self.function = Some(id);
+    }
+
+    /// Gives back what it was before, so it can be put back
+    pub fn set_can_retry(&mut self, can_retry: bool) -> bool {
+        std::mem::replace(&mut self.can_retry, can_retry)
+    }
+
+    pub fn can_retry(&self) -> bool {
+        self.can_retry
+    }
+
+    pub fn module(&self) -> Option<&Identifier> {
+        self.module.as_ref()
+    }
+
+    /// The name an item of this module goes by outside of it, like `geo::Point`
+    pub fn qualify(&self, name: &Identifier) -> Identifier {
+        match &self.module {
+            Some(module) => {
+                Identifier::new(format!("{}::{}", module.as_string(), name.as_string()))
+            }
+            None => name.clone(),
+        }
+    }
+
+    /// Types and generic instances are shared by every module, so they live at the top
+    pub fn root(&self) -> &SymbolTable<'a> {
+        match self.parent {
+            Some(parent) => parent.root(),
+            None => self,
+        }
+    }
+
+    /// The type of `"..."` literals, from the prelude
+    pub fn str_ty(&self) -> Ty {
+        let symbol = self
+            .root()
+            .symbols
+            .get(&Identifier::new("Str".to_string()))
+            .expect("Prelude was visited");
+        symbol
+            .as_struct()
+            .expect("Prelude was visited")
+            .ty(symbol.id())
+    }
+
+    pub fn legacy_truthiness(&self) -> bool {
+        self.legacy_truthiness
+    }
+
+    /// Keep an error to show once everything is checked,
+    /// one that only follows from an earlier error is left out
+    pub fn report(&self, error: SemanticError) {
+        if !error.kind.is_cascade() {
+            self.root().errors.borrow_mut().push(error);
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.root().errors.borrow().len()
+    }
+
+    pub fn take_errors(&mut self) -> Vec<SemanticError> {
+        self.errors.take()
+    }
+
+    pub fn warn(&self, warning: SemanticWarning) {
+        self.root().warnings.borrow_mut().push(warning);
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<SemanticWarning> {
+        self.warnings.take()
+    }
+
+    /// Leave out warnings from `lint` anywhere inside `span`
+    pub fn allow(&self, lint: Lint, span: Span) {
+        self.root().allowed.borrow_mut().push((lint, span));
+    }
+
+    pub fn take_allowed(&mut self) -> Vec<(Lint, Span)> {
+        self.allowed.take()
+    }
+
+    /// Warn with `lint` if the symbol is never used,
+    /// a name starting with `_` says that's on purpose
+    pub fn watch_uses(&self, lint: Lint, id: SymbolId, name: Identifier, span: Span) {
+        if !name.as_string().starts_with('_') {
+            self.root()
+                .watched
+                .borrow_mut()
+                .push((lint, id, name, span));
+        }
+    }
+
+    pub fn mark_used(&self, id: SymbolId) {
+        if self.function != Some(id) {
+            *self.root().uses.borrow_mut().entry(id).or_default() += 1;
+        }
+    }
+
+    /// Take back a use that turned out to only write to the symbol
+    pub fn unmark_used(&self, id: SymbolId) {
+        let mut uses = self.root().uses.borrow_mut();
+        if let Some(count) = uses.get_mut(&id) {
+            *count -= 1;
+            if *count == 0 {
+                uses.remove(&id);
+            }
+        }
+    }
+
+    /// Names of the watched symbols nothing ever used, with their lint and where they were declared
+    pub fn take_unused(&mut self) -> Vec<(Lint, Identifier, Span)> {
+        let uses = self.uses.take();
+        self.watched
+            .take()
+            .into_iter()
+            .filter(|(_, id, ..)| !uses.contains_key(id))
+            .map(|(lint, _, name, span)| (lint, name, span))
+            .collect()
+    }
+
+    /// Record a local that has its address taken, kept in the root table
+    pub fn mark_address_taken(&self, id: SymbolId) {
+        match self.parent {
+            Some(parent) => parent.mark_address_taken(id),
+            None => {
+                self.address_taken.borrow_mut().insert(id);
+            }
+        }
+    }
+
+    pub fn take_address_taken(&mut self) -> HashSet<SymbolId> {
+        self.address_taken.take()
+    }
+
+    pub fn declare_mutable(&self, id: SymbolId, name: Identifier, span: Span) {
+        self.root()
+            .mutable_locals
+            .borrow_mut()
+            .push((id, name, span));
+    }
+
+    /// Record a write to a `mut` local, or a `&mut` to it
+    pub fn mark_mutated(&self, id: SymbolId) {
+        self.root().mutated.borrow_mut().insert(id);
+    }
+
+    /// Names of the `mut` locals nothing ever changed, and where they were declared
+    pub fn take_unmutated(&mut self) -> Vec<(Identifier, Span)> {
+        let mutated = self.mutated.take();
+        self.mutable_locals
+            .take()
+            .into_iter()
+            .filter(|(id, ..)| !mutated.contains(id))
+            .map(|(_, name, span)| (name, span))
+            .collect()
+    }
+
+    pub fn set_ty_def(&self, symbol: Symbol) {
+        self.root().ty_defs.borrow_mut().insert(symbol.id(), symbol);
+    }
+
+    pub fn get_ty_def(&self, id: SymbolId) -> Option<Symbol> {
+        self.root().ty_defs.borrow().get(&id).cloned()
+    }
+
+    pub fn set_const_value(&self, id: SymbolId, value: Option<Literal>) {
+        self.root().const_values.borrow_mut().insert(id, value);
+    }
+
+    /// `None` until the const is checked, then its value if it has one
+    pub fn get_const_value(&self, id: SymbolId) -> Option<Option<Literal>> {
+        self.root().const_values.borrow().get(&id).copied()
+    }
+
+    pub fn get_laid_out(&self, ty: &Ty) -> Option<TyInfo> {
+        self.root().laid_out.borrow().get(ty).cloned()
+    }
+
+    pub fn set_laid_out(&self, ty: Ty, info: TyInfo) {
+        self.root().laid_out.borrow_mut().insert(ty, info);
+    }
+
+    /// `trait_` is the qualified name of the trait for methods from a trait impl
+    pub fn set_method(
+        &self,
+        ty: &Ty,
+        trait_: Option<Identifier>,
+        name: Identifier,
+        method: MethodInfo,
+    ) {
+        self.root()
+            .methods
+            .borrow_mut()
+            .entry(method_key(ty))
+            .or_default()
+            .insert((trait_, name), method);
+    }
+
+    pub fn get_method(
+        &self,
+        ty: &Ty,
+        trait_: Option<&Identifier>,
+        name: &Identifier,
+    ) -> Option<MethodInfo> {
+        self.root()
+            .methods
+            .borrow()
+            .get(&method_key(ty))?
+            .get(&(trait_.cloned(), name.clone()))
+            .cloned()
+    }
+
+    /// Every trait implemented for the type with a method of this name, along with that method
+    pub fn get_trait_methods(&self, ty: &Ty, name: &Identifier) -> Vec<(Identifier, MethodInfo)> {
+        let methods = self.root().methods.borrow();
+        let mut found = methods
+            .get(&method_key(ty))
+            .into_iter()
+            .flatten()
+            .filter_map(|((trait_, method), info)| match trait_ {
+                Some(trait_) if method == name => Some((trait_.clone(), info.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        found.sort_by(|(a, _), (b, _)| a.as_string().cmp(b.as_string()));
+        found
+    }
+
+    /// The instance being checked has `ty` as a type argument that had to implement `trait_`
+    pub fn add_bound(&mut self, ty: Ty, trait_: Identifier) {
+        self.bounds.push((method_key(&ty), trait_));
+    }
+
+    pub fn is_bound(&self, ty: &Ty, trait_: &Identifier) -> bool {
+        let ty = method_key(ty);
+        self.bounds
+            .iter()
+            .any(|bound| *bound == (ty.clone(), trait_.clone()))
+            || self
+                .parent
+                .is_some_and(|parent| parent.is_bound(&ty, trait_))
+    }
+
+    pub fn set_trait_impl(&self, trait_: SymbolId, ty: &Ty, info: TraitImpl) {
+        self.root()
+            .trait_impls
+            .borrow_mut()
+            .insert((trait_, method_key(ty)), info);
+    }
+
+    pub fn get_trait_impl(&self, trait_: SymbolId, ty: &Ty) -> Option<TraitImpl> {
+        self.root()
+            .trait_impls
+            .borrow()
+            .get(&(trait_, method_key(ty)))
+            .cloned()
+    }
+
+    /// Every instance of a generic type that got laid out, the rest are already items
+    pub fn take_ty_instances(&mut self) -> Vec<(Ty, TyInfo)> {
+        self.laid_out
+            .take()
+            .into_iter()
+            .filter(|(ty, _)| match ty {
+                Ty::Struct { args, .. } | Ty::Union { args, .. } => !args.is_empty(),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// The symbol of a generic function given these type arguments, queuing it to be checked if it's new
+    pub fn instantiate_func(&self, template: SymbolId, args: Vec<Ty>) -> SymbolId {
+        let mut instances = self.root().func_instances.borrow_mut();
+
+        if let Some(id) = instances.ids.get(&(template, args.clone())) {
+            return *id;
         }
+
+        let symbol_id = SymbolId::new();
+        instances.ids.insert((template, args.clone()), symbol_id);
+        instances.pending.push(FuncInstance {
+            template,
+            args,
+            symbol_id,
+        });
+        symbol_id
+    }
+
+    pub fn next_func_instance(&self) -> Option<FuncInstance> {
+        self.root().func_instances.borrow_mut().pending.pop()
     }
 
     pub fn set(&mut self, key: Identifier, symbol: Symbol) -> SymbolId {
@@ -30,13 +428,93 @@ impl<'a> SymbolTable<'a> {
         id
     }
 
+    /// Whether this scope itself has the name, ignoring its parents
+    pub fn declares(&self, key: &Identifier) -> bool {
+        self.symbols.contains_key(key)
+    }
+
     pub fn get(&self, key: &Identifier) -> Option<&Symbol> {
+        // A path looks through an imported module, which only has its public items
+        if let Some((module, rest)) = key.split_first() {
+            return self.get(&module)?.as_module()?.exports.get(&rest);
+        }
+
         match (self.symbols.get(key), self.parent) {
             (Some(value), _) => Some(value),
             (None, Some(parent)) => parent.get(key),
             _ => None,
         }
     }
+
+    /// Only looks in the current scope, parents can't be borrowed mutably
+    pub fn get_mut(&mut self, key: &Identifier) -> Option<&mut Symbol> {
+        self.symbols.get_mut(key)
+    }
+
+    /// What other modules get to see, once every item of this one has a symbol
+    pub fn module_info(&self, exports: &[Identifier]) -> ModuleInfo {
+        let exports = exports
+            .iter()
+            .map(|name| {
+                let symbol = self.symbols.get(name).expect("Item was declared");
+                (name.clone(), symbol.clone())
+            })
+            .collect::<HashMap<_, _>>();
+
+        ModuleInfo {
+            private: self
+                .symbols
+                .keys()
+                .filter(|name| !exports.contains_key(*name))
+                .cloned()
+                .collect(),
+            exports,
+        }
+    }
+
+    /// The label and the innermost loop are the same symbol, they share one type slot
+    pub fn set_loop(&mut self, label: Option<Identifier>, symbol: Symbol) -> SymbolId {
+        // Labels start with `'`, so they can't collide with other symbols
+        if let Some(label) = label {
+            self.set(label, symbol.clone());
+        }
+        self.set(innermost_loop(), symbol)
+    }
+
+    pub fn get_loop(&self, label: Option<&Identifier>) -> Option<&Symbol> {
+        match label {
+            Some(label) => self.get(label),
+            None => self.get(&innermost_loop()),
+        }
+    }
+}
+
+/// The trait a method came from, if it's from a trait impl, and its own name
+type MethodName = (Option<Identifier>, Identifier);
+
+/// Methods belong to a struct or union whatever its type arguments are, so those are left out
+fn method_key(ty: &Ty) -> Ty {
+    match ty {
+        Ty::Struct {
+            name, symbol_id, ..
+        } => Ty::Struct {
+            name: name.clone(),
+            symbol_id: *symbol_id,
+            args: Vec::new(),
+        },
+        Ty::Union {
+            name, symbol_id, ..
+        } => Ty::Union {
+            name: name.clone(),
+            symbol_id: *symbol_id,
+            args: Vec::new(),
+        },
+        ty => ty.clone(),
+    }
+}
+
+fn innermost_loop() -> Identifier {
+    Identifier::new("'".to_string())
 }
 
 #[derive(Debug, Clone)]
@@ -46,17 +524,129 @@ pub struct Symbol {
 }
 
 impl Symbol {
-    pub fn new_local(ty: Ty) -> Self {
+    pub fn new_local(ty: Ty, mutable: bool) -> Self {
         Self {
             id: SymbolId::new(),
-            kind: SymbolKind::Local(LocalInfo { ty }),
+            kind: SymbolKind::Local(LocalInfo { ty, mutable }),
         }
     }
 
     pub fn new_func(ty: Ty, params: Vec<Ty>) -> Self {
+        Self::new_generic_func(Vec::new(), Vec::new(), ty, params)
+    }
+
+    /// Signature types can use the type parameters, they're filled in at each call
+    pub fn new_generic_func(
+        ty_params: Vec<Identifier>,
+        bounds: Vec<Bound>,
+        ty: Ty,
+        params: Vec<Ty>,
+    ) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Func(FuncInfo {
+                ty_params,
+                bounds,
+                ty,
+                params,
+                variadic: false,
+            }),
+        }
+    }
+
+    /// A C function taking extra args after its params
+    pub fn new_variadic_func(ty: Ty, params: Vec<Ty>) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Func(FuncInfo {
+                ty_params: Vec::new(),
+                bounds: Vec::new(),
+                ty,
+                params,
+                variadic: true,
+            }),
+        }
+    }
+
+    /// The value is kept apart, see `SymbolTable::set_const_value`
+    pub fn new_const(ty: Ty) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Const(ty),
+        }
+    }
+
+    pub fn new_static(ty: Ty, mutable: bool) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Static(StaticInfo { ty, mutable }),
+        }
+    }
+
+    pub fn new_loop(kind: LoopKind) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Loop(LoopInfo {
+                kind,
+                ty: Rc::default(),
+            }),
+        }
+    }
+
+    /// Fields are filled in once every type in the file has a symbol
+    pub fn new_struct(name: Identifier, ty_params: Vec<Identifier>) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Struct(StructDef {
+                name,
+                ty_params,
+                fields: Vec::new(),
+            }),
+        }
+    }
+
+    /// Variants are filled in once every type in the file has a symbol
+    pub fn new_union(name: Identifier, ty_params: Vec<Identifier>) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Union(UnionDef {
+                name,
+                ty_params,
+                variants: Vec::new(),
+            }),
+        }
+    }
+
+    /// Methods are filled in once every type in the file has a symbol
+    pub fn new_trait(name: Identifier) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Trait(TraitDef {
+                name,
+                methods: Vec::new(),
+            }),
+        }
+    }
+
+    /// Another name for a type, used to bind type parameters
+    pub fn new_ty(ty: Ty) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Ty(ty),
+        }
+    }
+
+    pub fn new_variant(union: Ty, tag: u32) -> Self {
+        Self {
+            id: SymbolId::new(),
+            kind: SymbolKind::Variant(VariantRef { union, tag }),
+        }
+    }
+
+    pub fn new_module(info: ModuleInfo) -> Self {
         Self {
             id: SymbolId::new(),
-            kind: SymbolKind::Func(FuncInfo { ty, params }),
+            kind: SymbolKind::Module(info),
         }
     }
 
@@ -77,23 +667,314 @@ impl Symbol {
             _ => None,
         }
     }
+
+    pub fn as_const(&self) -> Option<&Ty> {
+        match &self.kind {
+            SymbolKind::Const(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    pub fn as_static(&self) -> Option<&StaticInfo> {
+        match &self.kind {
+            SymbolKind::Static(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn as_loop(&self) -> Option<&LoopInfo> {
+        match &self.kind {
+            SymbolKind::Loop(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&StructDef> {
+        match &self.kind {
+            SymbolKind::Struct(def) => Some(def),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct_mut(&mut self) -> Option<&mut StructDef> {
+        match &mut self.kind {
+            SymbolKind::Struct(def) => Some(def),
+            _ => None,
+        }
+    }
+
+    pub fn as_union(&self) -> Option<&UnionDef> {
+        match &self.kind {
+            SymbolKind::Union(def) => Some(def),
+            _ => None,
+        }
+    }
+
+    pub fn as_union_mut(&mut self) -> Option<&mut UnionDef> {
+        match &mut self.kind {
+            SymbolKind::Union(def) => Some(def),
+            _ => None,
+        }
+    }
+
+    pub fn as_trait(&self) -> Option<&TraitDef> {
+        match &self.kind {
+            SymbolKind::Trait(def) => Some(def),
+            _ => None,
+        }
+    }
+
+    pub fn as_trait_mut(&mut self) -> Option<&mut TraitDef> {
+        match &mut self.kind {
+            SymbolKind::Trait(def) => Some(def),
+            _ => None,
+        }
+    }
+
+    pub fn as_ty(&self) -> Option<&Ty> {
+        match &self.kind {
+            SymbolKind::Ty(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    pub fn as_variant(&self) -> Option<&VariantRef> {
+        match &self.kind {
+            SymbolKind::Variant(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn as_module(&self) -> Option<&ModuleInfo> {
+        match &self.kind {
+            SymbolKind::Module(info) => Some(info),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 enum SymbolKind {
     Local(LocalInfo),
     Func(FuncInfo),
+    Const(Ty),
+    Static(StaticInfo),
+    Loop(LoopInfo),
+    Struct(StructDef),
+    Union(UnionDef),
+    Trait(TraitDef),
+    Variant(VariantRef),
+    Ty(Ty),
+    Module(ModuleInfo),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct LocalInfo {
     pub ty: Ty,
+    /// Bound with `let mut` or a `mut` param
+    pub mutable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct StaticInfo {
+    pub ty: Ty,
+    pub mutable: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct FuncInfo {
+    /// Empty unless the function is generic
+    pub ty_params: Vec<Identifier>,
+    /// Checked against the type arguments of each instance
+    pub bounds: Vec<Bound>,
     pub ty: Ty,
     pub params: Vec<Ty>,
+    /// Declared from C with `...`, extra args follow the params
+    pub variadic: bool,
+}
+
+/// `T: Trait`, the type argument given for `T` needs an impl of the trait
+#[derive(Debug, Clone)]
+pub struct Bound {
+    pub param: Identifier,
+    /// Qualified with the module it was declared in
+    pub name: Identifier,
+    pub symbol_id: SymbolId,
+}
+
+/// A function from an `impl`, only reachable as `value.name(...)`
+#[derive(Debug, Clone)]
+pub struct MethodInfo {
+    /// The name it's linked with, like `geo::Point.len`
+    pub name: Identifier,
+    pub symbol: Symbol,
+    /// Local methods can only be called from the module that declared them
+    pub shared: bool,
+    pub module: Option<Identifier>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoopInfo {
+    pub kind: LoopKind,
+    /// Joined from every `break` that targets this loop, whichever name it used
+    pub ty: Rc<RefCell<Option<Ty>>>,
+}
+
+/// Fields as they were declared, any type parameters are still in them
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    /// Qualified with the module it was declared in
+    pub name: Identifier,
+    pub ty_params: Vec<Identifier>,
+    pub fields: Vec<(Identifier, Ty)>,
+}
+
+impl StructDef {
+    /// The struct with its own type parameters as the arguments
+    pub fn ty(&self, symbol_id: SymbolId) -> Ty {
+        Ty::Struct {
+            name: self.name.clone(),
+            symbol_id,
+            args: self.ty_params.iter().cloned().map(Ty::Param).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnionDef {
+    /// Qualified with the module it was declared in
+    pub name: Identifier,
+    pub ty_params: Vec<Identifier>,
+    pub variants: Vec<(Identifier, Vec<Ty>)>,
+}
+
+impl UnionDef {
+    /// The union with its own type parameters as the arguments
+    pub fn ty(&self, symbol_id: SymbolId) -> Ty {
+        Ty::Union {
+            name: self.name.clone(),
+            symbol_id,
+            args: self.ty_params.iter().cloned().map(Ty::Param).collect(),
+        }
+    }
+}
+
+/// Signatures use `Self` as a type parameter, an impl replaces it with its own type
+#[derive(Debug, Clone)]
+pub struct TraitDef {
+    /// Qualified with the module it was declared in
+    pub name: Identifier,
+    pub methods: Vec<TraitMethod>,
+}
+
+impl TraitDef {
+    /// Where the method's address is in a vtable, counting in pointers
+    pub fn method(&self, name: &Identifier) -> Option<(usize, &TraitMethod)> {
+        self.methods
+            .iter()
+            .enumerate()
+            .find(|(_, m)| m.name == *name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TraitMethod {
+    pub name: Identifier,
+    pub params: Vec<Ty>,
+    pub ty: Ty,
+}
+
+/// The methods of an impl in the order its trait declares them, which is also the vtable order
+#[derive(Debug, Clone)]
+pub struct TraitImpl {
+    /// Uses the impl's type parameters if it's generic
+    pub self_ty: Ty,
+    pub methods: Vec<MethodInfo>,
+}
+
+/// An imported module, written before the names in a path
+#[derive(Debug, Clone, Default)]
+pub struct ModuleInfo {
+    pub exports: HashMap<Identifier, Symbol>,
+    /// Everything else it has, only kept to give a better error
+    pub private: HashSet<Identifier>,
+}
+
+/// A struct or union with concrete types, ready to be used
+#[derive(Debug, Clone)]
+pub enum TyInfo {
+    Struct(StructInfo),
+    Union(UnionInfo),
+}
+
+impl TyInfo {
+    pub fn layout(&self) -> Layout {
+        match self {
+            TyInfo::Struct(info) => info.layout,
+            TyInfo::Union(info) => info.layout,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StructInfo {
+    pub fields: Vec<FieldInfo>,
+    pub layout: Layout,
+}
+
+impl StructInfo {
+    pub fn field(&self, name: &Identifier) -> Option<&FieldInfo> {
+        self.fields.iter().find(|f| f.name == *name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInfo {
+    pub name: Identifier,
+    pub ty: Ty,
+    /// Bytes from the start of the struct
+    pub offset: u32,
+}
+
+/// The tag is a `U32` at the very start, every payload shares the memory after it
+#[derive(Debug, Clone, Default)]
+pub struct UnionInfo {
+    pub variants: Vec<VariantInfo>,
+    pub layout: Layout,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantInfo {
+    pub name: Identifier,
+    /// Named by position, offsets are from the start of the union
+    pub fields: Vec<FieldInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantRef {
+    /// Uses the type parameters of the union if it's generic
+    pub union: Ty,
+    pub tag: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FuncInstances {
+    ids: HashMap<(SymbolId, Vec<Ty>), SymbolId>,
+    pending: Vec<FuncInstance>,
+}
+
+/// A generic function called with some type arguments, checked as its own function
+#[derive(Debug, Clone)]
+pub struct FuncInstance {
+    pub template: SymbolId,
+    pub args: Vec<Ty>,
+    pub symbol_id: SymbolId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopKind {
+    Loop,
+    While,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -2,26 +2,64 @@ use super::*;
 
 impl ast::Block {
     pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<Block, SemanticError> {
+        self.visit_header_expecting(symbol_table, None)
+    }
+
+    /// The trailing expression gets the hint, it's what the block gives back
+    pub fn visit_header_expecting(
+        self,
+        symbol_table: &mut SymbolTable,
+        expected: Option<&Ty>,
+    ) -> Result<Block, SemanticError> {
         let symbol_table = &mut symbol_table.fork();
 
-        let body = self
+        let body: Vec<_> = self
             .body
             .into_iter()
             .map(|s| s.visit_header(symbol_table))
             .collect::<Result<_, _>>()?;
 
+        // Without a trailing expression the block ends in `()`, found at its closing brace
+        let has_trailing = self.trailing.is_some();
+        let end = Span {
+            start: self.span.end - 1,
+            ..self.span
+        };
         let trailing = Box::new(
             self.trailing
                 .map(|t| *t)
-                .unwrap_or(ast::Expression::Literal(ast::Literal::I32(0)))
-                .visit_header(symbol_table)?,
+                .unwrap_or(ast::Expression {
+                    kind: ast::ExpressionKind::Literal(ast::Literal::Unit),
+                    span: end,
+                })
+                .visit_header_expecting(symbol_table, expected)?,
         );
 
-        // Blocks return their trailing expr, same goes for types
+        // Only the first unreachable part is pointed out, the rest follow from it
+        if let Some(position) = body.iter().position(Statement::diverges) {
+            let unreachable = match body.get(position + 1) {
+                Some(statement) => Some(statement.span()),
+                None if has_trailing => Some(trailing.span),
+                None => None,
+            };
+            if let Some(span) = unreachable {
+                symbol_table.warn(SemanticWarning::UnreachableCode { span });
+            }
+        }
+
+        // Blocks return their trailing expr, same goes for types,
+        // unless the block jumps away before it would get to `()`
+        let ty = if !has_trailing && body.iter().any(Statement::diverges) {
+            Ty::Never
+        } else {
+            trailing.ty.clone()
+        };
+
         Ok(Block {
             body,
-            ty: trailing.ty,
+            ty,
             trailing,
+            span: self.span,
         })
     }
 }
@@ -29,29 +67,48 @@ impl ast::Block {
 impl ast::Statement {
     pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<Statement, SemanticError> {
         Ok(match self {
-            ast::Statement::LetBinding { place, value, ty } => {
+            ast::Statement::LetBinding {
+                span,
+                mutable,
+                place,
+                value,
+                ty,
+            } => {
                 let place = place.visit_common();
-                let value = value.visit_header(symbol_table)?;
+                let declared = ty.map(|t| t.visit_common(symbol_table));
+                let value = value.visit_header_expecting(symbol_table, declared.as_ref())?;
 
                 // Infer type if not declared
-                let ty = if let Some(ty) = ty {
-                    ty.visit_common()
-                } else {
-                    value.ty
-                };
+                let ty = declared.unwrap_or_else(|| value.ty.clone());
+
+                // Assert types match, a jump away never gets to bind anything
+                if value.ty.join(&ty).as_ref() != Some(&ty) {
+                    symbol_table.report(
+                        SemanticError::from(SemanticErrorKind::TyMismatchDeclare {
+                            expected: ty.clone(),
+                            found: value.ty.clone(),
+                        })
+                        .at(value.span),
+                    );
+                }
 
-                // Assert types match
-                if ty != value.ty {
-                    return Err(SemanticError::TyMismatchDeclare {
-                        expected: ty,
-                        found: value.ty,
+                if symbol_table.declares(&place) {
+                    symbol_table.warn(SemanticWarning::Shadowing {
+                        symbol: place.clone(),
+                        span,
                     });
                 }
 
                 // Create a new symbol in the current scope
-                let symbol_id = symbol_table.set(place.clone(), Symbol::new_local(ty));
+                let symbol_id =
+                    symbol_table.set(place.clone(), Symbol::new_local(ty.clone(), mutable));
+                symbol_table.watch_uses(Lint::UnusedVariables, symbol_id, place.clone(), span);
+                if mutable {
+                    symbol_table.declare_mutable(symbol_id, place.clone(), span);
+                }
 
                 Statement::LetBinding {
+                    span,
                     place,
                     symbol_id,
                     ty,
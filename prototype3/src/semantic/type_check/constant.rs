@@ -0,0 +1,306 @@
+use super::*;
+
+/// Consts and statics need their value while compiling, so only literals, consts and operators are allowed
+pub fn visit_initializer(
+    value: ast::Expression,
+    ty: &Ty,
+    symbol_table: &mut SymbolTable,
+) -> Result<Literal, SemanticError> {
+    evaluate(&visit_checked(value, ty, symbol_table)?)
+}
+
+/// A static can also be an array or struct, as long as each part is constant
+pub fn visit_static_initializer(
+    value: ast::Expression,
+    ty: &Ty,
+    symbol_table: &mut SymbolTable,
+) -> Result<Constant, SemanticError> {
+    let value = visit_checked(value, ty, symbol_table)?;
+    evaluate_aggregate(&value, symbol_table)
+}
+
+fn visit_checked(
+    value: ast::Expression,
+    ty: &Ty,
+    symbol_table: &mut SymbolTable,
+) -> Result<Expression, SemanticError> {
+    let value = value.visit_header_expecting(symbol_table, Some(ty))?;
+
+    if value.ty != *ty {
+        return Err(SemanticErrorKind::TyMismatchInitializer {
+            expected: ty.clone(),
+            found: value.ty,
+        }
+        .into());
+    }
+
+    Ok(value)
+}
+
+fn evaluate_aggregate(
+    expr: &Expression,
+    symbol_table: &SymbolTable,
+) -> Result<Constant, SemanticError> {
+    use ExpressionKind::*;
+
+    let element_size = || match &expr.ty {
+        Ty::Array { of, .. } => symbol_table.ty_layout(of).map(|layout| layout.size),
+        _ => unreachable!("Only arrays have elements"),
+    };
+
+    Ok(match &expr.kind {
+        Block(block) if block.body.is_empty() => {
+            return evaluate_aggregate(&block.trailing, symbol_table)
+        }
+        StructLiteral { fields, .. } => Constant::Aggregate(
+            fields
+                .iter()
+                .map(|field| {
+                    Ok((
+                        field.offset,
+                        evaluate_aggregate(&field.value, symbol_table)?,
+                    ))
+                })
+                .collect::<Result<_, SemanticError>>()?,
+        ),
+        ArrayLiteral { values } => {
+            let size = element_size()?;
+            Constant::Aggregate(
+                values
+                    .iter()
+                    .zip((0..).step_by(size as usize))
+                    .map(|(value, offset)| Ok((offset, evaluate_aggregate(value, symbol_table)?)))
+                    .collect::<Result<_, SemanticError>>()?,
+            )
+        }
+        ArrayRepeat { value } => {
+            let size = element_size()?;
+            let len = match &expr.ty {
+                Ty::Array { len, .. } => *len,
+                _ => unreachable!("Only arrays repeat"),
+            };
+            let value = evaluate_aggregate(value, symbol_table)?;
+            Constant::Aggregate((0..len).map(|i| (i * size, value.clone())).collect())
+        }
+        _ => Constant::Scalar(evaluate(expr)?),
+    })
+}
+
+pub fn evaluate(expr: &Expression) -> Result<Literal, SemanticError> {
+    use ExpressionKind::*;
+
+    match &expr.kind {
+        Literal(literal) => Ok(*literal),
+        Block(block) if block.body.is_empty() => evaluate(&block.trailing),
+        PrefixCall { operator, value } => {
+            let value = Number::from(evaluate(value)?);
+            prefix(operator, value).into_literal(&expr.ty)
+        }
+        InfixCall {
+            left,
+            operator,
+            right,
+        } => {
+            let bits = left.ty.scalar_layout().map_or(0, |layout| layout.size * 8);
+            let left = Number::from(evaluate(left)?);
+            let right = Number::from(evaluate(right)?);
+            infix(operator, left, right, bits)?.into_literal(&expr.ty)
+        }
+        Cast { value, trapping } => {
+            let value = Number::from(evaluate(value)?);
+            let literal = value.into_literal(&expr.ty)?;
+            match (value, trapping) {
+                (Number::Float(float), true) => check_float_fits(float, &expr.ty)?,
+                (Number::Int(int), true) => check_int_fits(int, literal, &expr.ty)?,
+                _ => {}
+            }
+            Ok(literal)
+        }
+        _ => Err(SemanticErrorKind::NotConstant.into()),
+    }
+}
+
+/// The value of an integer expression, if it can be worked out while compiling
+pub fn known_integer(expr: &Expression) -> Option<i128> {
+    match evaluate(expr).map(Number::from) {
+        Ok(Number::Int(num)) => Some(num),
+        _ => None,
+    }
+}
+
+/// Every literal widened, integers keep their sign so they compare and divide the same way
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Unit,
+}
+
+impl From<Literal> for Number {
+    fn from(literal: Literal) -> Self {
+        use Literal::*;
+
+        match literal {
+            I8(num) => Number::Int(num.into()),
+            I16(num) => Number::Int(num.into()),
+            I32(num) => Number::Int(num.into()),
+            I64(num) => Number::Int(num.into()),
+            ISize(num) => Number::Int(num as i128),
+            U8(num) => Number::Int(num.into()),
+            U16(num) => Number::Int(num.into()),
+            U32(num) => Number::Int(num.into()),
+            U64(num) => Number::Int(num.into()),
+            USize(num) => Number::Int(num as i128),
+            F32(num) => Number::Float(num.into()),
+            F64(num) => Number::Float(num),
+            Bool(value) => Number::Bool(value),
+            Unit => Number::Unit,
+        }
+    }
+}
+
+impl Number {
+    /// Integers wrap and floats saturate, the same as they would at runtime
+    fn into_literal(self, ty: &Ty) -> Result<Literal, SemanticError> {
+        use Number::*;
+
+        Ok(match (ty, self) {
+            (Ty::I8, Int(num)) => Literal::I8(num as i8),
+            (Ty::I16, Int(num)) => Literal::I16(num as i16),
+            (Ty::I32, Int(num)) => Literal::I32(num as i32),
+            (Ty::I64, Int(num)) => Literal::I64(num as i64),
+            (Ty::ISize, Int(num)) => Literal::ISize(num as isize),
+            (Ty::U8, Int(num)) => Literal::U8(num as u8),
+            (Ty::U16, Int(num)) => Literal::U16(num as u16),
+            (Ty::U32, Int(num)) => Literal::U32(num as u32),
+            (Ty::U64, Int(num)) => Literal::U64(num as u64),
+            (Ty::USize, Int(num)) => Literal::USize(num as usize),
+            (Ty::F32, Int(num)) => Literal::F32(num as f32),
+            (Ty::F64, Int(num)) => Literal::F64(num as f64),
+
+            (Ty::I8, Float(num)) => Literal::I8(num as i8),
+            (Ty::I16, Float(num)) => Literal::I16(num as i16),
+            (Ty::I32, Float(num)) => Literal::I32(num as i32),
+            (Ty::I64, Float(num)) => Literal::I64(num as i64),
+            (Ty::ISize, Float(num)) => Literal::ISize(num as isize),
+            (Ty::U8, Float(num)) => Literal::U8(num as u8),
+            (Ty::U16, Float(num)) => Literal::U16(num as u16),
+            (Ty::U32, Float(num)) => Literal::U32(num as u32),
+            (Ty::U64, Float(num)) => Literal::U64(num as u64),
+            (Ty::USize, Float(num)) => Literal::USize(num as usize),
+            (Ty::F32, Float(num)) => Literal::F32(num as f32),
+            (Ty::F64, Float(num)) => Literal::F64(num),
+
+            (Ty::Bool, Bool(value)) => Literal::Bool(value),
+            (Ty::Unit, Unit) => Literal::Unit,
+            _ => unreachable!("Checked by type_check"),
+        })
+    }
+}
+
+fn prefix(operator: &PrefixOperator, value: Number) -> Number {
+    use {Number::*, PrefixOperator::*};
+
+    match (operator, value) {
+        (Negate, Int(num)) => Int(num.wrapping_neg()),
+        (Negate, Float(num)) => Float(-num),
+        (BitNot, Int(num)) => Int(!num),
+        (BooleanNot, Bool(value)) => Bool(!value),
+        _ => unreachable!("Checked by type_check"),
+    }
+}
+
+/// `bits` is the width of the operands, shifts only use that many bits of their amount
+fn infix(
+    operator: &InfixOperator,
+    left: Number,
+    right: Number,
+    bits: u32,
+) -> Result<Number, SemanticError> {
+    use {InfixOperator::*, Number::*};
+
+    Ok(match (left, right) {
+        (Int(_), Int(0)) if matches!(operator, Divide | Remainder) => {
+            return Err(SemanticErrorKind::ConstDivideByZero.into())
+        }
+        (Int(left), Int(right)) => match operator {
+            Add => Int(left.wrapping_add(right)),
+            Subtract => Int(left.wrapping_sub(right)),
+            Multiply => Int(left.wrapping_mul(right)),
+            Divide => Int(left.wrapping_div(right)),
+            Remainder => Int(left.wrapping_rem(right)),
+
+            BitAnd => Int(left & right),
+            BitOr => Int(left | right),
+            BitXor => Int(left ^ right),
+            ShiftLeft => Int(left.wrapping_shl(right as u32 % bits)),
+            ShiftRight => Int(left.wrapping_shr(right as u32 % bits)),
+
+            Equal => Bool(left == right),
+            NotEqual => Bool(left != right),
+            GreaterThan => Bool(left > right),
+            LessThan => Bool(left < right),
+            GreaterOrEqual => Bool(left >= right),
+            LessOrEqual => Bool(left <= right),
+
+            BooleanAnd | BooleanOr => unreachable!("Checked by type_check"),
+        },
+        (Float(left), Float(right)) => match operator {
+            Add => Float(left + right),
+            Subtract => Float(left - right),
+            Multiply => Float(left * right),
+            Divide => Float(left / right),
+
+            Equal => Bool(left == right),
+            NotEqual => Bool(left != right),
+            GreaterThan => Bool(left > right),
+            LessThan => Bool(left < right),
+            GreaterOrEqual => Bool(left >= right),
+            LessOrEqual => Bool(left <= right),
+
+            _ => unreachable!("Checked by type_check"),
+        },
+        (Bool(left), Bool(right)) => match operator {
+            BitAnd | BooleanAnd => Bool(left && right),
+            BitOr | BooleanOr => Bool(left || right),
+            BitXor | NotEqual => Bool(left != right),
+            Equal => Bool(left == right),
+            _ => unreachable!("Checked by type_check"),
+        },
+        (Unit, Unit) => Bool(matches!(operator, Equal)),
+        _ => unreachable!("Checked by type_check"),
+    })
+}
+
+/// Narrowing or changing sign has to give back the same number, the same as `as!` at runtime
+fn check_int_fits(int: i128, cast: Literal, ty: &Ty) -> Result<(), SemanticError> {
+    match Number::from(cast) {
+        Number::Int(back) if back != int => {
+            Err(SemanticErrorKind::ConstCastOutOfRange { ty: ty.clone() }.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// What `as!` would trap on at runtime stops the compile instead
+fn check_float_fits(float: f64, ty: &Ty) -> Result<(), SemanticError> {
+    let bits = match ty.scalar_layout() {
+        Some(layout) if ty.is_integer() => (layout.size * 8) as i32,
+        _ => return Ok(()),
+    };
+
+    let (min, end) = if ty.is_signed() {
+        (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1))
+    } else {
+        (0.0, 2f64.powi(bits))
+    };
+
+    // NaN fails both comparisons
+    let float = float.trunc();
+    if float >= min && float < end {
+        Ok(())
+    } else {
+        Err(SemanticErrorKind::ConstCastOutOfRange { ty: ty.clone() }.into())
+    }
+}
@@ -17,6 +17,8 @@ impl ast::Literal {
             Self::USize(num) => USize(num),
             Self::F32(num) => F32(num),
             Self::F64(num) => F64(num),
+            Self::Bool(value) => Bool(value),
+            Self::Unit => Unit,
         })
     }
 }
@@ -27,6 +29,8 @@ impl ast::PrefixOperator {
 
         Ok(match self {
             Self::Negate => Negate,
+            Self::BooleanNot => BooleanNot,
+            Self::BitNot => BitNot,
         })
     }
 }
@@ -40,6 +44,13 @@ impl ast::InfixOperator {
             Self::Subtract => Subtract,
             Self::Multiply => Multiply,
             Self::Divide => Divide,
+            Self::Remainder => Remainder,
+
+            Self::BitAnd => BitAnd,
+            Self::BitOr => BitOr,
+            Self::BitXor => BitXor,
+            Self::ShiftLeft => ShiftLeft,
+            Self::ShiftRight => ShiftRight,
 
             Self::Equal => Equal,
             Self::NotEqual => NotEqual,
@@ -47,6 +58,9 @@ impl ast::InfixOperator {
             Self::LessThan => LessThan,
             Self::GreaterOrEqual => GreaterOrEqual,
             Self::LessOrEqual => LessOrEqual,
+
+            Self::BooleanAnd => BooleanAnd,
+            Self::BooleanOr => BooleanOr,
         })
     }
 }
@@ -1,13 +1,53 @@
-use {super::*, std::cmp::Ordering};
+use {
+    super::{place::check_writable, *},
+    std::cmp::Ordering,
+};
 
 impl ast::Expression {
     pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<Expression, SemanticError> {
+        self.visit_header_expecting(symbol_table, None)
+    }
+
+    /// `expected` is the type the surrounding code wants, only used to infer type arguments
+    pub fn visit_header_expecting(
+        self,
+        symbol_table: &mut SymbolTable,
+        expected: Option<&Ty>,
+    ) -> Result<Expression, SemanticError> {
+        let span = self.span;
+        let checked = self.try_visit_header_expecting(symbol_table, expected);
+        recover(symbol_table, checked, span)
+    }
+
+    /// Gives back the error instead of reporting it, for when the caller might try again
+    fn try_visit_header_expecting(
+        self,
+        symbol_table: &mut SymbolTable,
+        expected: Option<&Ty>,
+    ) -> Result<Expression, SemanticError> {
+        let span = self.span;
+        let expression = self
+            .kind
+            .visit_header_expecting(symbol_table, expected, span)
+            .map_err(|error| error.at(span))?;
+        Ok(Expression { span, ..expression })
+    }
+}
+
+impl ast::ExpressionKind {
+    fn visit_header_expecting(
+        self,
+        symbol_table: &mut SymbolTable,
+        expected: Option<&Ty>,
+        span: Span,
+    ) -> Result<Expression, SemanticError> {
         use ExpressionKind::*;
 
         Ok(match self {
             Self::Literal(literal) => {
                 let literal = literal.visit_header(symbol_table)?;
                 Expression {
+                    span,
                     ty: literal.into(),
                     kind: Literal(literal),
                 }
@@ -20,18 +60,59 @@ impl ast::Expression {
                 let symbol =
                     symbol_table
                         .get(&place)
-                        .ok_or_else(|| SemanticError::LocalNotFound {
+                        .ok_or_else(|| SemanticErrorKind::LocalNotFound {
                             symbol: place.clone(),
                         })?;
+                symbol_table.mark_used(symbol.id());
+
+                // Variants without a payload are written as just their name
+                if symbol.as_variant().is_some() {
+                    return visit_variant_literal(symbol_table, place, Vec::new(), expected, span);
+                }
+
+                if let Some(func) = symbol.as_func() {
+                    let func = func.clone();
+                    return func_value(symbol_table, place, symbol.id(), func, expected, span);
+                }
+
+                if let Some(ty) = symbol.as_const() {
+                    let value = match symbol_table.get_const_value(symbol.id()) {
+                        Some(Some(value)) => value,
+                        // Its initializer was already reported
+                        Some(None) => return Err(SemanticErrorKind::AlreadyReported.into()),
+                        None => {
+                            return Err(SemanticErrorKind::ConstUsedBeforeValue {
+                                symbol: place.clone(),
+                            }
+                            .into())
+                        }
+                    };
+                    return Ok(Expression {
+                        span,
+                        ty: ty.clone(),
+                        kind: Literal(value),
+                    });
+                }
+
+                // Only a `static mut` gives a `*mut`, so only it can be written
+                if let Some(info) = symbol.as_static() {
+                    return Ok(Expression {
+                        span,
+                        ty: info.ty.clone(),
+                        kind: Deref(Box::new(static_address(place, symbol.id(), info, span))),
+                    });
+                }
 
                 let ty = symbol
                     .as_local()
-                    .ok_or_else(|| SemanticError::ExpectedLocalSymbol {
+                    .ok_or_else(|| SemanticErrorKind::ExpectedLocalSymbol {
                         symbol: place.clone(),
                     })?
-                    .ty;
+                    .ty
+                    .clone();
 
                 Expression {
+                    span,
                     ty,
                     kind: Lookup {
                         place,
@@ -43,125 +124,200 @@ impl ast::Expression {
             Self::Block(block) => {
                 let block = block.visit_header(symbol_table)?;
                 Expression {
-                    ty: block.ty,
+                    span,
+                    ty: block.ty.clone(),
                     kind: Block(block),
                 }
             }
 
-            Self::Assignment { place, value } => {
-                let place = place.visit_common();
+            Self::Assignment {
+                place,
+                operator,
+                value,
+            } => {
+                let place = place.visit_header(symbol_table)?;
 
-                // Lookup symbol
-                let symbol =
-                    symbol_table
-                        .get(&place)
-                        .ok_or_else(|| SemanticError::LocalNotFound {
-                            symbol: place.clone(),
-                        })?;
+                // Giving a local a new value doesn't use it, `x += 1` only reads it to write it back
+                if let Lookup { symbol_id, .. } = place.kind {
+                    symbol_table.unmark_used(symbol_id);
+                }
 
-                let ty = symbol
-                    .as_local()
-                    .ok_or_else(|| SemanticError::ExpectedLocalSymbol {
-                        symbol: place.clone(),
-                    })?
-                    .ty;
+                let place = place
+                    .into_place(true, symbol_table)?
+                    .ok_or(SemanticErrorKind::NotPlace)?;
+                let ty = place.ty().clone();
+                symbol_table.ty_layout(&ty)?;
 
-                let symbol_id = symbol.id();
-                let value = value.visit_header(symbol_table)?;
+                let value = value.visit_header_expecting(symbol_table, Some(&ty))?;
+                let operator = operator.map(|o| o.visit_header(symbol_table)).transpose()?;
 
-                // Assert types match
-                if ty != value.ty {
-                    return Err(SemanticError::TyMismatchAssign {
+                // `place op= value` has to give back what the place already holds
+                let found = match &operator {
+                    Some(operator) => infix_ty(symbol_table, operator, &ty, &value.ty)?,
+                    None => value.ty.clone(),
+                };
+                if ty != found {
+                    return Err(SemanticErrorKind::TyMismatchAssign {
                         expected: ty,
-                        found: value.ty,
-                    });
+                        found,
+                    }
+                    .into());
                 }
 
-                Expression {
-                    ty,
-                    kind: Assignment {
-                        place,
-                        value: Box::new(value),
-                        symbol_id,
-                    },
-                }
+                place.assign(operator, value)
             }
 
             Self::FuncCall { name, args } => {
+                let callee = name.clone();
                 let name = name.visit_common();
 
                 // Lookup symbol
                 let symbol =
                     symbol_table
                         .get(&name)
-                        .ok_or_else(|| SemanticError::FuncNotFound {
+                        .ok_or_else(|| SemanticErrorKind::FuncNotFound {
                             symbol: name.clone(),
                         })?;
+                symbol_table.mark_used(symbol.id());
 
-                let func = symbol
-                    .as_func()
-                    .ok_or_else(|| SemanticError::ExpectedFuncSymbol {
-                        symbol: name.clone(),
-                    })?;
+                // A function kept in a local or static is called through its value
+                if symbol.as_local().is_some() || symbol.as_static().is_some() {
+                    let callee = Box::new(ast::Expression {
+                        kind: ast::ExpressionKind::Lookup(callee),
+                        span,
+                    });
+                    return ast::ExpressionKind::Call { callee, args }.visit_header_expecting(
+                        symbol_table,
+                        expected,
+                        span,
+                    );
+                }
 
-                let symbol_id = symbol.id();
-                let ty = func.ty;
-                let params = func.params.clone();
+                let func =
+                    symbol
+                        .as_func()
+                        .ok_or_else(|| SemanticErrorKind::ExpectedFuncSymbol {
+                            symbol: name.clone(),
+                        })?;
 
-                let args = args
-                    .into_iter()
-                    .map(|a| a.visit_header(symbol_table))
-                    .collect::<Result<Vec<_>, _>>()?;
+                let (symbol_id, func) = (symbol.id(), func.clone());
+                visit_func_call(
+                    symbol_table,
+                    name,
+                    symbol_id,
+                    func,
+                    None,
+                    args,
+                    expected,
+                    span,
+                )?
+            }
 
-                // Make sure arg and param size match
-                match args.len().cmp(&params.len()) {
-                    Ordering::Less => {
-                        return Err(SemanticError::NotEnoughArgs {
-                            expected: params.len(),
-                            found: args.len(),
-                        })
-                    }
-                    Ordering::Greater => {
-                        return Err(SemanticError::TooManyArgs {
-                            expected: params.len(),
-                            found: args.len(),
-                        })
+            Self::Call { callee, args } => {
+                let callee = match *callee {
+                    // A method of the type wins, otherwise the field has to hold a function
+                    ast::Expression {
+                        kind: ast::ExpressionKind::FieldAccess { value, field },
+                        span: callee_span,
+                    } => {
+                        let value = value.visit_header(symbol_table)?;
+                        let field = field.visit_common();
+
+                        if let Ty::Dyn { .. } = value.ty {
+                            return visit_dyn_call(symbol_table, value, field, args);
+                        }
+
+                        if let Some(method) = lookup_method(symbol_table, &value.ty, &field)? {
+                            return visit_method_call(symbol_table, value, method, args, expected);
+                        }
+
+                        let info =
+                            lookup_field(symbol_table, &value.ty, &field).map_err(|error| {
+                                match *error.kind {
+                                    SemanticErrorKind::FieldNotFound { .. }
+                                    | SemanticErrorKind::FieldOnNonStruct { .. } => {
+                                        SemanticErrorKind::MethodNotFound {
+                                            found: value.ty.clone(),
+                                            method: field.clone(),
+                                        }
+                                        .into()
+                                    }
+                                    _ => error,
+                                }
+                            })?;
+
+                        Expression {
+                            span: callee_span,
+                            ty: info.ty,
+                            kind: FieldAccess {
+                                value: Box::new(value),
+                                field,
+                                offset: info.offset,
+                            },
+                        }
                     }
-                    Ordering::Equal => { /* Check's out, do nothing */ }
-                }
+                    callee => callee.visit_header(symbol_table)?,
+                };
 
-                // Make sure arg and param types match
-                for (position, (found, expected)) in args
-                    .iter()
-                    .map(|a| a.ty)
-                    .zip(params.into_iter())
+                let (params, ty) = match &callee.ty {
+                    Ty::Func { params, ret } => (params.clone(), *ret.clone()),
+                    _ => return Err(SemanticErrorKind::CallNonFunc { found: callee.ty }.into()),
+                };
+
+                check_arg_count(args.len(), params.len())?;
+
+                // Function values are never generic, so there's nothing to work out
+                let args = args
+                    .into_iter()
+                    .zip(params)
                     .enumerate()
-                {
-                    if found != expected {
-                        return Err(SemanticError::TyMismatchArg {
-                            expected,
-                            found,
+                    .map(|(position, (arg, param))| {
+                        let span = arg.span;
+                        let checked = visit_arg(
+                            symbol_table,
+                            arg,
+                            &param,
                             position,
-                        });
-                    }
-                }
+                            &mut Substitution::new(),
+                        );
+                        recover(symbol_table, checked, span)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
 
                 Expression {
+                    span,
                     ty,
-                    kind: FuncCall {
-                        name,
+                    kind: CallIndirect {
+                        callee: Box::new(callee),
                         args,
-                        symbol_id,
                     },
                 }
             }
 
             Self::PrefixCall { operator, value } => {
+                let operator = operator.visit_header(symbol_table)?;
                 let value = value.visit_header(symbol_table)?;
+
+                if operator.is_integer_only() && value.ty.is_float() {
+                    return Err(SemanticErrorKind::IntegerOnlyOperator {
+                        operator: operator.token(),
+                        ty: value.ty,
+                    }
+                    .into());
+                }
+
+                let ty = operator.result_ty(&value.ty).ok_or_else(|| {
+                    SemanticErrorKind::TyMismatchPrefix {
+                        operator: operator.clone(),
+                        value: value.ty.clone(),
+                    }
+                })?;
+
                 Expression {
-                    ty: value.ty,
+                    span,
+                    ty,
                     kind: PrefixCall {
-                        operator: operator.visit_header(symbol_table)?,
+                        operator,
                         value: Box::new(value),
                     },
                 }
@@ -175,17 +331,11 @@ impl ast::Expression {
                 let operator = operator.visit_header(symbol_table)?;
                 let left = left.visit_header(symbol_table)?;
                 let right = right.visit_header(symbol_table)?;
-
-                if left.ty != right.ty {
-                    return Err(SemanticError::TyMismatchOperator {
-                        operator,
-                        left: left.ty,
-                        right: right.ty,
-                    });
-                }
+                let ty = infix_ty(symbol_table, &operator, &left.ty, &right.ty)?;
 
                 Expression {
-                    ty: left.ty,
+                    span,
+                    ty,
                     kind: InfixCall {
                         operator,
                         left: Box::new(left),
@@ -199,56 +349,1674 @@ impl ast::Expression {
                 when_true,
                 when_false,
             } => {
-                let when_true = when_true.visit_header(symbol_table)?;
+                let when_true = when_true.visit_header_expecting(symbol_table, expected)?;
 
-                let when_false = if let Some(when_false) = when_false {
-                    when_false.visit_header(symbol_table)?
-                } else {
-                    ty_to_default_block(when_true.ty)
+                // Without a hint the other branch can still follow the first one
+                let hint = expected.or(Some(&when_true.ty).filter(|ty| **ty != Ty::Never));
+                let when_false = match when_false {
+                    Some(when_false) => when_false.visit_header_expecting(symbol_table, hint)?,
+                    // Leaving out the else only works if there's nothing to give back
+                    None if matches!(when_true.ty, Ty::Unit | Ty::Never) => unit_block(span),
+                    None => {
+                        return Err(SemanticErrorKind::MissingElse {
+                            when_true: when_true.ty,
+                        }
+                        .into())
+                    }
                 };
 
-                if when_true.ty != when_false.ty {
-                    return Err(SemanticError::TyMismatchIfElse {
-                        when_true: when_true.ty,
-                        when_false: when_false.ty,
-                    });
-                }
+                // A branch that jumps away fits whatever the other one gives
+                let ty = when_true.ty.join(&when_false.ty).ok_or_else(|| {
+                    SemanticErrorKind::TyMismatchIfElse {
+                        when_true: when_true.ty.clone(),
+                        when_false: when_false.ty.clone(),
+                    }
+                })?;
+
+                let predicate = predicate.visit_header(symbol_table)?;
+                check_predicate(&predicate, symbol_table)?;
 
                 Expression {
-                    ty: when_true.ty,
+                    span,
+                    ty,
                     kind: IfElse {
-                        predicate: Box::new(predicate.visit_header(symbol_table)?),
+                        predicate: Box::new(predicate),
                         when_true,
                         when_false,
                     },
                 }
             }
+
+            Self::Loop { label, body } => {
+                let symbol_table = &mut symbol_table.fork();
+                let symbol_id = symbol_table.set_loop(
+                    label.map(|l| l.visit_common()),
+                    Symbol::new_loop(LoopKind::Loop),
+                );
+
+                let body = body.visit_header(symbol_table)?;
+
+                // Breaks decided the type, a loop without any never finishes
+                let ty = symbol_table
+                    .get_loop(None)
+                    .and_then(Symbol::as_loop)
+                    .and_then(|info| info.ty.borrow().clone())
+                    .unwrap_or(Ty::Never);
+
+                Expression {
+                    span,
+                    ty,
+                    kind: Loop { body, symbol_id },
+                }
+            }
+
+            Self::While {
+                label,
+                predicate,
+                body,
+            } => {
+                let predicate = Box::new(predicate.visit_header(symbol_table)?);
+                check_predicate(&predicate, symbol_table)?;
+
+                let symbol_table = &mut symbol_table.fork();
+                let symbol_id = symbol_table.set_loop(
+                    label.map(|l| l.visit_common()),
+                    Symbol::new_loop(LoopKind::While),
+                );
+
+                Expression {
+                    span,
+                    ty: Ty::Unit,
+                    kind: While {
+                        predicate,
+                        body: body.visit_header(symbol_table)?,
+                        symbol_id,
+                    },
+                }
+            }
+
+            Self::Break { label, value } => {
+                let has_value = value.is_some();
+                let value = value
+                    .map(|v| *v)
+                    .unwrap_or(ast::Expression {
+                        kind: ast::ExpressionKind::Literal(ast::Literal::Unit),
+                        span,
+                    })
+                    .visit_header(symbol_table)?;
+
+                let symbol = match lookup_loop(symbol_table, label, "break") {
+                    Ok(symbol) => symbol,
+                    Err(error) => return Ok(failed_jump(symbol_table, error, span)),
+                };
+                let info = symbol.as_loop().expect("Label should be a loop");
+
+                // A jump with the wrong value still jumps, so what follows it stays unreachable
+                if has_value && info.kind == LoopKind::While {
+                    symbol_table
+                        .report(SemanticError::from(SemanticErrorKind::BreakValueInWhile).at(span));
+                }
+
+                // Every break has to agree on the type of the loop
+                let mut ty = info.ty.borrow_mut();
+                let joined =
+                    match &*ty {
+                        Some(expected) => expected.join(&value.ty).ok_or_else(|| {
+                            SemanticErrorKind::TyMismatchBreak {
+                                expected: expected.clone(),
+                                found: value.ty.clone(),
+                            }
+                        }),
+                        None => Ok(value.ty.clone()),
+                    };
+                match joined {
+                    Ok(joined) => *ty = Some(joined),
+                    Err(error) => symbol_table.report(SemanticError::from(error).at(span)),
+                }
+
+                Expression {
+                    span,
+                    ty: Ty::Never,
+                    kind: Break {
+                        value: Box::new(value),
+                        symbol_id: symbol.id(),
+                    },
+                }
+            }
+
+            Self::Continue { label } => match lookup_loop(symbol_table, label, "continue") {
+                Ok(symbol) => Expression {
+                    span,
+                    ty: Ty::Never,
+                    kind: Continue {
+                        symbol_id: symbol.id(),
+                    },
+                },
+                Err(error) => failed_jump(symbol_table, error, span),
+            },
+
+            Self::Return { value } => {
+                let expected = symbol_table
+                    .return_ty()
+                    .cloned()
+                    .expect("Every expression is inside a function");
+
+                let value = value
+                    .map(|v| *v)
+                    .unwrap_or(ast::Expression {
+                        kind: ast::ExpressionKind::Literal(ast::Literal::Unit),
+                        span,
+                    })
+                    .visit_header_expecting(symbol_table, Some(&expected))?;
+
+                // Reported here, the function's own check would find it again if this didn't diverge
+                if value.ty.join(&expected) != Some(expected.clone()) {
+                    symbol_table.report(
+                        SemanticError::from(SemanticErrorKind::TyMismatchReturn {
+                            expected,
+                            found: value.ty.clone(),
+                        })
+                        .at(span),
+                    );
+                }
+
+                Expression {
+                    span,
+                    ty: Ty::Never,
+                    kind: Return {
+                        value: Box::new(value),
+                    },
+                }
+            }
+
+            Self::AddressOf { mutable, place } => place
+                .visit_header(symbol_table)?
+                .into_place(mutable, symbol_table)?
+                .ok_or(SemanticErrorKind::NotPlace)?
+                .address(symbol_table),
+
+            Self::Deref(pointer) => {
+                let pointer = pointer.visit_header(symbol_table)?;
+
+                let ty = pointer
+                    .ty
+                    .pointee()
+                    .ok_or_else(|| SemanticErrorKind::DerefNonPointer {
+                        found: pointer.ty.clone(),
+                    })?
+                    .clone();
+
+                // Nothing else might have needed the layout of what's behind it yet
+                symbol_table.ty_layout(&ty)?;
+
+                Expression {
+                    span,
+                    ty,
+                    kind: Deref(Box::new(pointer)),
+                }
+            }
+
+            Self::StringLiteral {
+                bytes,
+                nul_terminated,
+            } => {
+                let ty = if nul_terminated {
+                    Ty::Pointer {
+                        mutable: false,
+                        to: Box::new(Ty::U8),
+                    }
+                } else {
+                    symbol_table.str_ty()
+                };
+
+                Expression {
+                    span,
+                    ty,
+                    kind: StringLiteral {
+                        bytes,
+                        nul_terminated,
+                    },
+                }
+            }
+
+            Self::Cast {
+                value,
+                trapping,
+                ty,
+            } => {
+                let ty = ty.visit_common(symbol_table);
+                let value = value.visit_header(symbol_table)?;
+
+                // A pointer to a value with an impl of the trait becomes a trait object
+                if let (Ty::Dyn { .. }, Ty::Pointer { .. }, false) = (&ty, &value.ty, trapping) {
+                    return make_dyn(symbol_table, value, ty);
+                }
+
+                if !value.ty.is_numeric() || !ty.is_numeric() {
+                    return Err(SemanticErrorKind::InvalidCast {
+                        from: value.ty,
+                        to: ty,
+                    }
+                    .into());
+                }
+
+                Expression {
+                    span,
+                    ty,
+                    kind: Cast {
+                        value: Box::new(value),
+                        trapping,
+                    },
+                }
+            }
+
+            Self::StructLiteral { name, fields } => {
+                let name = name.visit_common();
+
+                // Lookup symbol
+                let symbol =
+                    symbol_table
+                        .get(&name)
+                        .ok_or_else(|| SemanticErrorKind::TyNotFound {
+                            symbol: name.clone(),
+                        })?;
+
+                let def = symbol
+                    .as_struct()
+                    .ok_or_else(|| SemanticErrorKind::ExpectedStructSymbol {
+                        symbol: name.clone(),
+                    })?
+                    .clone();
+
+                let symbol_id = symbol.id();
+                let template = def.ty(symbol_id);
+
+                // Type arguments come from the fields, or failing that the surrounding code
+                let mut substitution = Substitution::new();
+                if let Some(expected) = expected {
+                    template.unify(expected, &mut substitution);
+                }
+
+                // Each bad field is reported on its own, the rest are still checked
+                let mut checked = Vec::<(Identifier, Expression)>::with_capacity(fields.len());
+                for f in fields {
+                    let field_span = f.span;
+                    let field = f.name.visit_common();
+
+                    let field_ty = match def.fields.iter().find(|(name, _)| *name == field) {
+                        Some((_, field_ty)) => field_ty,
+                        None => {
+                            symbol_table.report(
+                                SemanticError::from(SemanticErrorKind::FieldNotFound {
+                                    name: name.clone(),
+                                    field,
+                                })
+                                .at(field_span),
+                            );
+                            f.value.visit_header(symbol_table)?;
+                            continue;
+                        }
+                    };
+
+                    // Every field has to be given exactly once
+                    if checked.iter().any(|(f, _)| *f == field) {
+                        symbol_table.report(
+                            SemanticError::from(SemanticErrorKind::DuplicateField {
+                                name: name.clone(),
+                                field,
+                            })
+                            .at(field_span),
+                        );
+                        f.value.visit_header(symbol_table)?;
+                        continue;
+                    }
+
+                    let value =
+                        visit_field_value(symbol_table, f.value, field_ty, &mut substitution);
+                    checked.push((field, recover(symbol_table, value, field_span)?));
+                }
+                let fields = checked;
+
+                for (missing, _) in def
+                    .fields
+                    .iter()
+                    .filter(|(field, _)| fields.iter().all(|(f, _)| f != field))
+                {
+                    symbol_table.report(
+                        SemanticError::from(SemanticErrorKind::MissingField {
+                            name: name.clone(),
+                            field: missing.clone(),
+                        })
+                        .at(span),
+                    );
+                }
+
+                let ty = concrete(&template, &def.ty_params, &substitution, &name)?;
+                let info = symbol_table.struct_info(&ty)?;
+
+                let fields = fields
+                    .into_iter()
+                    .map(|(name, value)| FieldInit {
+                        offset: info.field(&name).expect("Field was checked").offset,
+                        name,
+                        value,
+                    })
+                    .collect();
+
+                Expression {
+                    span,
+                    ty,
+                    kind: StructLiteral { fields, symbol_id },
+                }
+            }
+
+            Self::FieldAccess { value, field } => {
+                let value = value.visit_header(symbol_table)?;
+                let field = field.visit_common();
+                let info = lookup_field(symbol_table, &value.ty, &field)?;
+
+                Expression {
+                    span,
+                    ty: info.ty,
+                    kind: FieldAccess {
+                        value: Box::new(value),
+                        field,
+                        offset: info.offset,
+                    },
+                }
+            }
+
+            Self::VariantLiteral { name, values } => {
+                return visit_variant_literal(
+                    symbol_table,
+                    name.visit_common(),
+                    values,
+                    expected,
+                    span,
+                )
+            }
+
+            Self::ArrayLiteral { values } => {
+                let hint = element_hint(expected);
+                let values = values
+                    .into_iter()
+                    .map(|v| v.visit_header_expecting(symbol_table, hint))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // The first element that checked decides, an empty array has to be told
+                let first = values
+                    .iter()
+                    .find(|v| v.ty != Ty::Error)
+                    .or_else(|| values.first());
+                let of = match first {
+                    Some(first) => first.ty.clone(),
+                    None => hint.cloned().ok_or(SemanticErrorKind::CantInferArrayTy)?,
+                };
+
+                for (position, value) in values.iter().enumerate().filter(|(_, v)| v.ty != of) {
+                    symbol_table.report(
+                        SemanticError::from(SemanticErrorKind::TyMismatchArrayElement {
+                            expected: of.clone(),
+                            found: value.ty.clone(),
+                            position,
+                        })
+                        .at(value.span),
+                    );
+                }
+
+                let ty = Ty::Array {
+                    of: Box::new(of),
+                    len: values.len() as u32,
+                };
+                symbol_table.ty_layout(&ty)?;
+
+                Expression {
+                    span,
+                    ty,
+                    kind: ArrayLiteral { values },
+                }
+            }
+
+            Self::ArrayRepeat { value, len } => {
+                let value = value.visit_header_expecting(symbol_table, element_hint(expected))?;
+
+                let ty = Ty::Array {
+                    of: Box::new(value.ty.clone()),
+                    len,
+                };
+                symbol_table.ty_layout(&ty)?;
+
+                Expression {
+                    span,
+                    ty,
+                    kind: ArrayRepeat {
+                        value: Box::new(value),
+                    },
+                }
+            }
+
+            Self::Index { value, index } => {
+                let value = value.visit_header(symbol_table)?;
+                let ty = element_ty(&value.ty)?.clone();
+                let index = visit_index(*index, &value.ty, symbol_table)?;
+
+                Expression {
+                    span,
+                    ty,
+                    kind: Index {
+                        value: Box::new(value),
+                        index: Box::new(index),
+                    },
+                }
+            }
+
+            Self::Slice {
+                mutable,
+                value,
+                start,
+                end,
+            } => {
+                let value = value.visit_header(symbol_table)?;
+                let of = element_ty(&value.ty)?.clone();
+                if mutable {
+                    check_writable(&value, symbol_table)?;
+                }
+
+                // Either end can be the length, so only the type is checked here
+                let mut visit_end = |end: Option<Box<ast::Expression>>| {
+                    end.map(|end| {
+                        let end = end.visit_header(symbol_table)?;
+                        if !end.ty.is_integer() {
+                            return Err(SemanticError::from(SemanticErrorKind::TyMismatchIndex {
+                                found: end.ty,
+                            })
+                            .at(end.span));
+                        }
+                        Ok(Box::new(end))
+                    })
+                    .transpose()
+                };
+                let start = visit_end(start)?;
+                let end = visit_end(end)?;
+
+                Expression {
+                    span,
+                    ty: Ty::Slice {
+                        mutable,
+                        of: Box::new(of),
+                    },
+                    kind: Slice {
+                        value: Box::new(value),
+                        start,
+                        end,
+                    },
+                }
+            }
+
+            Self::Match { value, arms } => {
+                let value = value.visit_header(symbol_table)?;
+                let variants = match &value.ty {
+                    Ty::Union { .. } => symbol_table.union_info(&value.ty)?.variants,
+                    _ => Vec::new(),
+                };
+
+                let mut covered = vec![false; variants.len()];
+                let mut has_wildcard = false;
+                let mut failed = false;
+                let mut ty: Option<Ty> = None;
+                let mut checked_arms = Vec::with_capacity(arms.len());
+
+                for (position, arm) in arms.into_iter().enumerate() {
+                    let symbol_table = &mut symbol_table.fork();
+
+                    let pattern = visit_pattern(
+                        symbol_table,
+                        arm.pattern,
+                        &value.ty,
+                        &variants,
+                        &mut covered,
+                        &mut has_wildcard,
+                        position + 1,
+                    );
+                    let pattern = match pattern {
+                        Ok(pattern) => pattern,
+                        // The arm is left out, the ones after it are still checked
+                        Err(error) => {
+                            symbol_table.report(error.at(arm.span));
+                            failed = true;
+                            continue;
+                        }
+                    };
+
+                    // Later arms follow the first one when there's nothing else to go on
+                    let hint = expected.or(ty
+                        .as_ref()
+                        .filter(|ty| **ty != Ty::Never && !ty.has_error()));
+                    let arm_value = arm.value.visit_header_expecting(symbol_table, hint)?;
+
+                    // The first arm decides the type of the match, arms that jump away fit any
+                    ty = match ty {
+                        Some(expected) => match expected.join(&arm_value.ty) {
+                            Some(joined) => Some(joined),
+                            None => {
+                                symbol_table.report(
+                                    SemanticError::from(SemanticErrorKind::TyMismatchMatch {
+                                        expected: expected.clone(),
+                                        found: arm_value.ty.clone(),
+                                    })
+                                    .at(arm_value.span),
+                                );
+                                Some(expected)
+                            }
+                        },
+                        None => Some(arm_value.ty.clone()),
+                    };
+
+                    checked_arms.push(MatchArm {
+                        pattern,
+                        value: arm_value,
+                    });
+                }
+
+                // An arm that failed may have been meant to cover what looks missing
+                if !has_wildcard && !failed {
+                    let missing = variants
+                        .iter()
+                        .zip(covered)
+                        .filter(|(_, covered)| !covered)
+                        .map(|(variant, _)| variant.name.clone())
+                        .collect::<Vec<_>>();
+
+                    // Nothing but a union can be matched without a wildcard
+                    if !missing.is_empty() || !matches!(value.ty, Ty::Union { .. }) {
+                        return Err(SemanticErrorKind::NonExhaustiveMatch { missing }.into());
+                    }
+                }
+
+                // Without any arms there's nothing it could give back
+                Expression {
+                    span,
+                    ty: ty.unwrap_or(if failed { Ty::Error } else { Ty::Never }),
+                    kind: Match {
+                        value: Box::new(value),
+                        arms: checked_arms,
+                        variants: variants.len() as u32,
+                    },
+                }
+            }
+
+            // Its syntax error is already reported
+            Self::Error => Expression {
+                span,
+                ty: Ty::Error,
+                kind: Error,
+            },
         })
     }
 }
 
-fn ty_to_default_block(ty: Ty) -> Block {
-    use Literal::*;
-    let literal = match ty {
-        Ty::I8 => I8(0),
-        Ty::I16 => I16(0),
-        Ty::I32 => I32(0),
-        Ty::I64 => I64(0),
-        Ty::ISize => ISize(0),
-        Ty::U8 => U8(0),
-        Ty::U16 => U16(0),
-        Ty::U32 => U32(0),
-        Ty::U64 => U64(0),
-        Ty::USize => USize(0),
-        Ty::F32 => F32(0.0),
-        Ty::F64 => F64(0.0),
+/// An expression that fails is reported and checking carries on, anything that uses it only sees `Ty::Error`.
+/// While the arg it's in can be checked again, a type argument that can't be inferred is given back instead
+fn recover(
+    symbol_table: &SymbolTable,
+    checked: Result<Expression, SemanticError>,
+    span: Span,
+) -> Result<Expression, SemanticError> {
+    match checked {
+        Err(error)
+            if symbol_table.can_retry()
+                && matches!(*error.kind, SemanticErrorKind::CantInferTyParam { .. }) =>
+        {
+            Err(error)
+        }
+        Err(error) => {
+            symbol_table.report(error.at(span));
+            Ok(Expression {
+                span,
+                ty: Ty::Error,
+                kind: ExpressionKind::Error,
+            })
+        }
+        checked => checked,
+    }
+}
+
+fn check_predicate(
+    predicate: &Expression,
+    symbol_table: &SymbolTable,
+) -> Result<(), SemanticError> {
+    match &predicate.ty {
+        Ty::Bool => Ok(()),
+        // Legacy mode treats any non-zero integer as true
+        ty if ty.is_integer() && symbol_table.legacy_truthiness() => Ok(()),
+        found => Err(SemanticErrorKind::TyMismatchPredicate {
+            found: found.clone(),
+        }
+        .into()),
+    }
+}
+
+fn lookup_loop<'t>(
+    symbol_table: &'t SymbolTable,
+    label: Option<ast::Identifier>,
+    keyword: &'static str,
+) -> Result<&'t Symbol, SemanticError> {
+    let label = label.map(|l| l.visit_common());
+
+    symbol_table.get_loop(label.as_ref()).ok_or(match label {
+        Some(label) => SemanticErrorKind::LabelNotFound { label }.into(),
+        None => SemanticErrorKind::JumpOutsideLoop { keyword }.into(),
+    })
+}
+
+/// A jump with nowhere to go is reported, but it still diverges so the code after it doesn't look reachable
+fn failed_jump(symbol_table: &SymbolTable, error: SemanticError, span: Span) -> Expression {
+    symbol_table.report(error.at(span));
+    Expression {
+        span,
+        ty: Ty::Never,
+        kind: ExpressionKind::Error,
+    }
+}
+
+/// Make sure arg and param size match
+fn check_arg_count(found: usize, expected: usize) -> Result<(), SemanticError> {
+    match found.cmp(&expected) {
+        Ordering::Less => Err(SemanticErrorKind::NotEnoughArgs { expected, found }.into()),
+        Ordering::Greater => Err(SemanticErrorKind::TooManyArgs { expected, found }.into()),
+        Ordering::Equal => Ok(()),
+    }
+}
+
+/// Make sure arg and param types match, working out any type arguments on the way,
+/// a method's receiver is already checked and goes first
+#[allow(clippy::too_many_arguments)]
+fn visit_func_call(
+    symbol_table: &mut SymbolTable,
+    name: Identifier,
+    symbol_id: SymbolId,
+    func: FuncInfo,
+    receiver: Option<Expression>,
+    args: Vec<ast::Expression>,
+    expected: Option<&Ty>,
+    span: Span,
+) -> Result<Expression, SemanticError> {
+    let FuncInfo {
+        ty_params,
+        bounds,
+        ty,
+        params,
+        variadic,
+    } = func;
+    let mut params = params.into_iter();
+    let mut substitution = Substitution::new();
+
+    // The receiver was made to fit `self`, it can only fill in type arguments
+    if let Some(receiver) = &receiver {
+        let param = params.next().expect("Methods take `self` first");
+        param.unify(&receiver.ty, &mut substitution);
+    }
+    let params = params.collect::<Vec<_>>();
+
+    let mut args = args;
+    let extra_args = if variadic && args.len() > params.len() {
+        args.split_off(params.len())
+    } else {
+        Vec::new()
     };
-    Block {
+    check_arg_count(args.len(), params.len())?;
+
+    let mut checked = vec![None; args.len()];
+    let mut deferred = Vec::new();
+
+    // An arg like `Nothing`, even deep inside another expression, can't say what it is,
+    // so let the others go first. Only once this call can't try again is that an error
+    let could_retry = symbol_table.set_can_retry(true);
+    let first_tries = args
+        .into_iter()
+        .zip(params)
+        .enumerate()
+        .map(|(position, (arg, param))| {
+            let checked = visit_arg(
+                symbol_table,
+                arg.clone(),
+                &param,
+                position,
+                &mut substitution,
+            );
+            let result = recover(symbol_table, checked, arg.span);
+            (position, arg, param, result)
+        })
+        .collect::<Vec<_>>();
+    symbol_table.set_can_retry(could_retry);
+
+    // Anything else wrong with an arg is already reported
+    for (position, arg, param, result) in first_tries {
+        match result {
+            Ok(arg) => checked[position] = Some(arg),
+            Err(_) => deferred.push((position, arg, param)),
+        }
+    }
+
+    for (position, arg, param) in deferred {
+        let span = arg.span;
+        let arg = visit_arg(symbol_table, arg, &param, position, &mut substitution);
+        checked[position] = Some(recover(symbol_table, arg, span)?);
+    }
+
+    let mut args = receiver
+        .into_iter()
+        .chain(
+            checked
+                .into_iter()
+                .map(|a| a.expect("Every arg was checked")),
+        )
+        .collect::<Vec<_>>();
+
+    if variadic {
+        let position = args.len();
+        for (i, arg) in extra_args.into_iter().enumerate() {
+            let span = arg.span;
+            let arg = visit_variadic_arg(symbol_table, arg, position + i);
+            args.push(recover(symbol_table, arg, span)?);
+        }
+
+        return Ok(Expression {
+            span,
+            ty,
+            kind: ExpressionKind::CallVariadic { args, symbol_id },
+        });
+    }
+
+    // Generic functions get their own copy for these type arguments
+    let (ty, symbol_id) = if ty_params.is_empty() {
+        (ty, symbol_id)
+    } else {
+        // Anything the args left open might still come from the surrounding code
+        if let Some(expected) = expected {
+            ty.unify(expected, &mut substitution);
+        }
+
+        // An arg that failed may be what would have decided a type argument
+        let instance = instantiate(
+            symbol_table,
+            &name,
+            symbol_id,
+            ty_params,
+            &bounds,
+            &substitution,
+            span,
+        );
+        let instance = match instance {
+            Err(_) if args.iter().any(|arg| arg.ty.has_error()) => {
+                return Err(SemanticErrorKind::AlreadyReported.into())
+            }
+            instance => instance?,
+        };
+        (ty.substitute(&substitution), instance)
+    };
+
+    Ok(Expression {
+        span,
         ty,
+        kind: ExpressionKind::FuncCall {
+            name,
+            args,
+            symbol_id,
+        },
+    })
+}
+
+/// A method of the value's type, or of the type it points to
+fn lookup_method(
+    symbol_table: &SymbolTable,
+    ty: &Ty,
+    name: &Identifier,
+) -> Result<Option<MethodInfo>, SemanticError> {
+    let ty = ty.pointee().unwrap_or(ty);
+    let found = symbol_table.get_trait_methods(ty, name);
+    let method = match lookup_bound_method(symbol_table, ty, name, &found)? {
+        Some(method) => method,
+        None => match symbol_table.get_method(ty, None, name) {
+            Some(method) => method,
+            None => match lookup_trait_method(ty, name, found)? {
+                Some(method) => method,
+                None => return Ok(None),
+            },
+        },
+    };
+
+    if !method.shared && method.module.as_ref() != symbol_table.module() {
+        return Err(SemanticErrorKind::PrivateMethod {
+            method: method.name,
+        }
+        .into());
+    }
+    symbol_table.mark_used(method.symbol.id());
+    Ok(Some(method))
+}
+
+/// Inside a generic function, a type parameter's bound is what its body was written against,
+/// so the bound's trait comes before the type's own impls
+fn lookup_bound_method(
+    symbol_table: &SymbolTable,
+    ty: &Ty,
+    name: &Identifier,
+    found: &[(Identifier, MethodInfo)],
+) -> Result<Option<MethodInfo>, SemanticError> {
+    let mut bound = found
+        .iter()
+        .filter(|(trait_, _)| symbol_table.is_bound(ty, trait_))
+        .cloned()
+        .collect::<Vec<_>>();
+    if bound.len() > 1 {
+        return Err(SemanticErrorKind::AmbiguousMethod {
+            method: name.clone(),
+            ty: ty.clone(),
+            traits: bound.into_iter().map(|(trait_, _)| trait_).collect(),
+        }
+        .into());
+    }
+    Ok(bound.pop().map(|(_, method)| method))
+}
+
+/// Without a bound or an impl of its own, the type's traits can give the method,
+/// as long as only one of them has the name
+fn lookup_trait_method(
+    ty: &Ty,
+    name: &Identifier,
+    mut found: Vec<(Identifier, MethodInfo)>,
+) -> Result<Option<MethodInfo>, SemanticError> {
+    if found.len() > 1 {
+        return Err(SemanticErrorKind::AmbiguousMethod {
+            method: name.clone(),
+            ty: ty.clone(),
+            traits: found.into_iter().map(|(trait_, _)| trait_).collect(),
+        }
+        .into());
+    }
+    Ok(found.pop().map(|(_, method)| method))
+}
+
+/// `value.name(...)`, the value is passed as `self` after taking its address or dereferencing it
+fn visit_method_call(
+    symbol_table: &mut SymbolTable,
+    value: Expression,
+    method: MethodInfo,
+    args: Vec<ast::Expression>,
+    expected: Option<&Ty>,
+) -> Result<Expression, SemanticError> {
+    let span = value.span;
+    let func = method
+        .symbol
+        .as_func()
+        .expect("Methods are functions")
+        .clone();
+    let by_pointer = match &func.params[0] {
+        Ty::Pointer { mutable, .. } => Some(*mutable),
+        _ => None,
+    };
+
+    let receiver = match (by_pointer, value.ty.pointee().cloned()) {
+        (None, None) => value,
+        (None, Some(ty)) => Expression {
+            span,
+            ty,
+            kind: ExpressionKind::Deref(Box::new(value)),
+        },
+        (Some(mutable), Some(to)) => receiver_pointer(value, mutable, to)?,
+        (Some(mutable), None) => match value.into_place(mutable, symbol_table)? {
+            Some(place) => place.address(symbol_table),
+            None => {
+                return Err(SemanticErrorKind::ReceiverNotPlace {
+                    method: method.name,
+                }
+                .into())
+            }
+        },
+    };
+
+    let symbol_id = method.symbol.id();
+    visit_func_call(
+        symbol_table,
+        method.name,
+        symbol_id,
+        func,
+        Some(receiver),
+        args,
+        expected,
+        span,
+    )
+}
+
+/// The trait a `dyn` type was made from
+fn dyn_trait(symbol_table: &SymbolTable, ty: &Ty) -> TraitDef {
+    match ty {
+        Ty::Dyn { symbol_id, .. } => symbol_table
+            .get_ty_def(*symbol_id)
+            .and_then(|symbol| symbol.as_trait().cloned())
+            .expect("Trait was declared"),
+        _ => unreachable!("Only called with `dyn` types"),
+    }
+}
+
+/// Through a vtable only the address of the value is known, so that's all `self` can be
+fn check_object_safe(def: &TraitDef) -> Result<(), SemanticError> {
+    let self_ty = Ty::Param(Identifier::new("Self".to_string()));
+
+    for method in def.methods.iter() {
+        let by_pointer = matches!(&method.params[0], Ty::Pointer { to, .. } if **to == self_ty);
+        let elsewhere = method.params[1..]
+            .iter()
+            .chain(Some(&method.ty))
+            .any(Ty::has_params);
+
+        if !by_pointer || elsewhere {
+            return Err(SemanticErrorKind::TraitNotObjectSafe {
+                trait_: def.name.clone(),
+                method: method.name.clone(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// `pointer as dyn Trait`, the vtable holds the impl's methods for the type behind the pointer
+fn make_dyn(
+    symbol_table: &SymbolTable,
+    pointer: Expression,
+    ty: Ty,
+) -> Result<Expression, SemanticError> {
+    let span = pointer.span;
+    let def = dyn_trait(symbol_table, &ty);
+    check_object_safe(&def)?;
+
+    let (trait_id, mutable) = match ty {
+        Ty::Dyn {
+            symbol_id, mutable, ..
+        } => (symbol_id, mutable),
+        _ => unreachable!("Only called with `dyn` types"),
+    };
+    let (to, pointer_mutable) = match &pointer.ty {
+        Ty::Pointer { to, mutable } => (*to.clone(), *mutable),
+        _ => unreachable!("Only called with pointers"),
+    };
+
+    if mutable && !pointer_mutable {
+        return Err(SemanticErrorKind::AssignThroughConstPointer { found: pointer.ty }.into());
+    }
+
+    let info = symbol_table.get_trait_impl(trait_id, &to).ok_or_else(|| {
+        SemanticErrorKind::TraitNotImplemented {
+            trait_: def.name.clone(),
+            ty: to.clone(),
+        }
+    })?;
+
+    // Methods of a generic impl need the copy for this instance
+    let mut substitution = Substitution::new();
+    info.self_ty.unify(&to, &mut substitution);
+
+    // Every method that can't be made for this type is its own error
+    let mut methods = Vec::with_capacity(info.methods.len());
+    let mut failed = false;
+    for method in info.methods {
+        let func = method.symbol.as_func().expect("Methods are functions");
+        if func.ty_params.is_empty() {
+            methods.push(method.symbol.id());
+            continue;
+        }
+
+        let instance = instantiate(
+            symbol_table,
+            &method.name,
+            method.symbol.id(),
+            func.ty_params.clone(),
+            &func.bounds,
+            &substitution,
+            span,
+        );
+        match instance {
+            Ok(symbol_id) => methods.push(symbol_id),
+            Err(error) => {
+                symbol_table.report(error.at(span));
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err(SemanticErrorKind::AlreadyReported.into());
+    }
+
+    Ok(Expression {
+        span,
+        ty,
+        kind: ExpressionKind::MakeDyn {
+            pointer: Box::new(pointer),
+            methods,
+        },
+    })
+}
+
+/// `object.name(...)` on a trait object, the method is found in its vtable while running
+fn visit_dyn_call(
+    symbol_table: &mut SymbolTable,
+    object: Expression,
+    name: Identifier,
+    args: Vec<ast::Expression>,
+) -> Result<Expression, SemanticError> {
+    let span = object.span;
+    let def = dyn_trait(symbol_table, &object.ty);
+    check_object_safe(&def)?;
+
+    let (index, method) = def
+        .method(&name)
+        .ok_or_else(|| SemanticErrorKind::MethodNotFound {
+            found: object.ty.clone(),
+            method: name.clone(),
+        })?;
+
+    let self_mutable = match &method.params[0] {
+        Ty::Pointer { mutable, .. } => *mutable,
+        _ => unreachable!("Checked to be object safe"),
+    };
+    if let (true, Ty::Dyn { mutable: false, .. }) = (self_mutable, &object.ty) {
+        return Err(SemanticErrorKind::MutMethodOnConstDyn {
+            method: name,
+            found: object.ty,
+        }
+        .into());
+    }
+
+    let params = &method.params[1..];
+    check_arg_count(args.len(), params.len())?;
+
+    // Object safe methods only use `Self` for `self`, so nothing else needs working out
+    let args = args
+        .into_iter()
+        .zip(params)
+        .enumerate()
+        .map(|(position, (arg, param))| {
+            let span = arg.span;
+            let checked = visit_arg(symbol_table, arg, param, position, &mut Substitution::new());
+            recover(symbol_table, checked, span)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Expression {
+        span,
+        ty: method.ty.clone(),
+        kind: ExpressionKind::CallDyn {
+            object: Box::new(object),
+            index: index as u32,
+            args,
+        },
+    })
+}
+
+/// A `*mut` pointer works where a `*` one is wanted, not the other way around
+fn receiver_pointer(
+    pointer: Expression,
+    mutable: bool,
+    to: Ty,
+) -> Result<Expression, SemanticError> {
+    if let Ty::Pointer { mutable: false, .. } = pointer.ty {
+        if mutable {
+            return Err(SemanticErrorKind::AssignThroughConstPointer { found: pointer.ty }.into());
+        }
+    }
+
+    Ok(Expression {
+        span: pointer.span,
+        ty: Ty::Pointer {
+            mutable,
+            to: Box::new(to),
+        },
+        kind: pointer.kind,
+    })
+}
+
+/// The copy of a generic function for these type arguments, every one of them has to be known
+/// and have an impl of each trait it's bound by. Past the first, what's wrong is reported at `span`
+fn instantiate(
+    symbol_table: &SymbolTable,
+    name: &Identifier,
+    symbol_id: SymbolId,
+    ty_params: Vec<Identifier>,
+    bounds: &[Bound],
+    substitution: &Substitution,
+    span: Span,
+) -> Result<SymbolId, SemanticError> {
+    let mut missing = ty_params
+        .iter()
+        .filter(|param| !substitution.contains_key(*param))
+        .map(|param| {
+            SemanticError::from(SemanticErrorKind::CantInferTyParam {
+                param: param.clone(),
+                name: name.clone(),
+            })
+        });
+    if let Some(first) = missing.next() {
+        // Once the args are checked again the others might be known after all
+        if !symbol_table.can_retry() {
+            missing.for_each(|error| symbol_table.report(error.at(span)));
+        }
+        return Err(first);
+    }
+    let ty_args = ty_params
+        .iter()
+        .map(|param| substitution[param].clone())
+        .collect::<Vec<_>>();
+
+    // An argument that failed to check already has its error
+    if ty_args.iter().any(Ty::has_error) {
+        return Err(SemanticErrorKind::AlreadyReported.into());
+    }
+
+    if ty_args.iter().any(|a| a.depth() > MAX_TY_DEPTH) {
+        return Err(SemanticErrorKind::InstantiationTooDeep { name: name.clone() }.into());
+    }
+
+    let mut unimplemented = bounds.iter().filter_map(|bound| {
+        let ty = &substitution[&bound.param];
+        match symbol_table.get_trait_impl(bound.symbol_id, ty) {
+            Some(_) => None,
+            None => Some(SemanticError::from(
+                SemanticErrorKind::TraitNotImplemented {
+                    trait_: bound.name.clone(),
+                    ty: ty.clone(),
+                },
+            )),
+        }
+    });
+    if let Some(first) = unimplemented.next() {
+        unimplemented.for_each(|error| symbol_table.report(error.at(span)));
+        return Err(first);
+    }
+
+    Ok(symbol_table.instantiate_func(symbol_id, ty_args))
+}
+
+/// A function named without calling it, a generic one takes its type arguments from the surrounding code
+fn func_value(
+    symbol_table: &SymbolTable,
+    name: Identifier,
+    symbol_id: SymbolId,
+    func: FuncInfo,
+    expected: Option<&Ty>,
+    span: Span,
+) -> Result<Expression, SemanticError> {
+    // A function type has no room for the extra args
+    if func.variadic {
+        return Err(SemanticErrorKind::VariadicFuncValue { name }.into());
+    }
+
+    let ty = Ty::Func {
+        params: func.params,
+        ret: Box::new(func.ty),
+    };
+
+    if func.ty_params.is_empty() {
+        return Ok(Expression {
+            span,
+            ty,
+            kind: ExpressionKind::FuncAddress { symbol_id },
+        });
+    }
+
+    let mut substitution = Substitution::new();
+    if let Some(expected) = expected {
+        ty.unify(expected, &mut substitution);
+    }
+
+    Ok(Expression {
+        span,
+        ty: ty.substitute(&substitution),
+        kind: ExpressionKind::FuncAddress {
+            symbol_id: instantiate(
+                symbol_table,
+                &name,
+                symbol_id,
+                func.ty_params,
+                &func.bounds,
+                &substitution,
+                span,
+            )?,
+        },
+    })
+}
+
+/// Nothing says what type an extra arg should be, C then widens it the usual way:
+/// floats to `F64` and anything smaller than an int to `I32`
+fn visit_variadic_arg(
+    symbol_table: &mut SymbolTable,
+    arg: ast::Expression,
+    position: usize,
+) -> Result<Expression, SemanticError> {
+    let arg = arg.visit_header(symbol_table)?;
+    let span = arg.span;
+
+    let ty = match &arg.ty {
+        Ty::F32 => Ty::F64,
+        Ty::I8 | Ty::I16 | Ty::U8 | Ty::U16 | Ty::Bool => Ty::I32,
+        ty if ty.is_numeric() || ty.is_pointer() || matches!(ty, Ty::Func { .. }) => {
+            return Ok(arg)
+        }
+        _ => {
+            return Err(SemanticError::from(SemanticErrorKind::InvalidVariadicArg {
+                found: arg.ty,
+                position,
+            })
+            .at(span))
+        }
+    };
+
+    Ok(Expression {
+        span,
+        ty,
+        kind: ExpressionKind::Cast {
+            value: Box::new(arg),
+            trapping: false,
+        },
+    })
+}
+
+/// Check an argument against its parameter, learning any type arguments it decides
+fn visit_arg(
+    symbol_table: &mut SymbolTable,
+    arg: ast::Expression,
+    param: &Ty,
+    position: usize,
+    substitution: &mut Substitution,
+) -> Result<Expression, SemanticError> {
+    let hint = param.substitute(substitution);
+    let hint = Some(&hint).filter(|h| !h.has_params());
+
+    // Whether it can't be inferred yet is up to the caller
+    let arg = arg.try_visit_header_expecting(symbol_table, hint)?;
+
+    if !param.unify(&arg.ty, substitution) {
+        return Err(SemanticError::from(SemanticErrorKind::TyMismatchArg {
+            expected: param.substitute(substitution),
+            found: arg.ty,
+            position,
+        })
+        .at(arg.span));
+    }
+    Ok(arg)
+}
+
+/// A field of a struct or variant literal, learning any type arguments it decides
+fn visit_field_value(
+    symbol_table: &mut SymbolTable,
+    value: ast::Expression,
+    field_ty: &Ty,
+    substitution: &mut Substitution,
+) -> Result<Expression, SemanticError> {
+    let hint = field_ty.substitute(substitution);
+    let hint = Some(&hint).filter(|h| !h.has_params());
+    let value = value.visit_header_expecting(symbol_table, hint)?;
+
+    if !field_ty.unify(&value.ty, substitution) {
+        return Err(SemanticErrorKind::TyMismatchAssign {
+            expected: field_ty.substitute(substitution),
+            found: value.ty,
+        }
+        .into());
+    }
+    Ok(value)
+}
+
+/// Check an arm's pattern against the matched type and what earlier arms already cover,
+/// the payload it names is bound as new locals. The arm's `position` counts from 1
+fn visit_pattern(
+    symbol_table: &mut SymbolTable,
+    pattern: ast::Pattern,
+    ty: &Ty,
+    variants: &[VariantInfo],
+    covered: &mut [bool],
+    has_wildcard: &mut bool,
+    position: usize,
+) -> Result<Pattern, SemanticError> {
+    Ok(match pattern {
+        ast::Pattern::Wildcard => {
+            if *has_wildcard || (!covered.is_empty() && covered.iter().all(|c| *c)) {
+                return Err(SemanticErrorKind::UnreachableArm { position }.into());
+            }
+            *has_wildcard = true;
+            Pattern::Wildcard
+        }
+
+        ast::Pattern::Variant { name, bindings } => {
+            let name = name.visit_common();
+            let variant = lookup_variant(symbol_table, &name)?;
+
+            if !same_ty_def(&variant.union, ty) {
+                return Err(SemanticErrorKind::TyMismatchPattern {
+                    found: ty.clone(),
+                    variant: name,
+                }
+                .into());
+            }
+
+            let tag = variant.tag;
+            if *has_wildcard || covered[tag as usize] {
+                return Err(SemanticErrorKind::UnreachableArm { position }.into());
+            }
+            covered[tag as usize] = true;
+
+            let fields = &variants[tag as usize].fields;
+            if bindings.len() != fields.len() {
+                return Err(SemanticErrorKind::WrongPayloadCount {
+                    variant: name,
+                    expected: fields.len(),
+                    found: bindings.len(),
+                }
+                .into());
+            }
+
+            // Bind each named part of the payload as a new local
+            let bindings = bindings
+                .into_iter()
+                .zip(fields)
+                .filter_map(|(binding, field)| {
+                    let symbol_id = symbol_table.set(
+                        binding?.visit_common(),
+                        Symbol::new_local(field.ty.clone(), false),
+                    );
+                    Some(Binding {
+                        symbol_id,
+                        ty: field.ty.clone(),
+                        offset: field.offset,
+                    })
+                })
+                .collect();
+
+            Pattern::Variant { tag, bindings }
+        }
+    })
+}
+
+fn lookup_variant(
+    symbol_table: &SymbolTable,
+    name: &Identifier,
+) -> Result<VariantRef, SemanticError> {
+    symbol_table
+        .get(name)
+        .ok_or_else(|| SemanticErrorKind::VariantNotFound {
+            symbol: name.clone(),
+        })?
+        .as_variant()
+        .cloned()
+        .ok_or_else(|| {
+            SemanticErrorKind::ExpectedVariantSymbol {
+                symbol: name.clone(),
+            }
+            .into()
+        })
+}
+
+fn visit_variant_literal(
+    symbol_table: &mut SymbolTable,
+    name: Identifier,
+    values: Vec<ast::Expression>,
+    expected: Option<&Ty>,
+    span: Span,
+) -> Result<Expression, SemanticError> {
+    let variant = lookup_variant(symbol_table, &name)?;
+
+    let (union_name, def) = match &variant.union {
+        Ty::Union {
+            name, symbol_id, ..
+        } => (
+            name.clone(),
+            symbol_table
+                .get_ty_def(*symbol_id)
+                .as_ref()
+                .and_then(Symbol::as_union)
+                .expect("Union was declared")
+                .clone(),
+        ),
+        _ => unreachable!("Variants belong to a union"),
+    };
+
+    let (_, field_tys) = &def.variants[variant.tag as usize];
+    if values.len() != field_tys.len() {
+        return Err(SemanticErrorKind::WrongPayloadCount {
+            variant: name,
+            expected: field_tys.len(),
+            found: values.len(),
+        }
+        .into());
+    }
+
+    // Type arguments come from the payload, or failing that the surrounding code
+    let mut substitution = Substitution::new();
+    if let Some(expected) = expected {
+        variant.union.unify(expected, &mut substitution);
+    }
+
+    let values = values
+        .into_iter()
+        .zip(field_tys)
+        .map(|(value, field_ty)| {
+            let span = value.span;
+            let value = visit_field_value(symbol_table, value, field_ty, &mut substitution);
+            recover(symbol_table, value, span)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ty = concrete(&variant.union, &def.ty_params, &substitution, &union_name)?;
+    let info = symbol_table.union_info(&ty)?;
+
+    let fields = values
+        .into_iter()
+        .zip(info.variants[variant.tag as usize].fields.iter())
+        .map(|(value, field)| FieldInit {
+            name: field.name.clone(),
+            value,
+            offset: field.offset,
+        })
+        .collect();
+
+    Ok(Expression {
+        span,
+        ty,
+        kind: ExpressionKind::VariantLiteral {
+            tag: variant.tag,
+            fields,
+        },
+    })
+}
+
+/// Fill in the type arguments of a generic struct or union, all of them have to be known by now
+fn concrete(
+    template: &Ty,
+    ty_params: &[Identifier],
+    substitution: &Substitution,
+    name: &Identifier,
+) -> Result<Ty, SemanticError> {
+    if let Some(param) = ty_params.iter().find(|p| !substitution.contains_key(p)) {
+        return Err(SemanticErrorKind::CantInferTyParam {
+            param: param.clone(),
+            name: name.clone(),
+        }
+        .into());
+    }
+    Ok(template.substitute(substitution))
+}
+
+/// Whether two types come from the same declaration, whatever their type arguments are
+fn same_ty_def(left: &Ty, right: &Ty) -> bool {
+    match (left, right) {
+        (Ty::Struct { symbol_id: l, .. }, Ty::Struct { symbol_id: r, .. })
+        | (Ty::Union { symbol_id: l, .. }, Ty::Union { symbol_id: r, .. }) => l == r,
+        _ => false,
+    }
+}
+
+fn lookup_field(
+    symbol_table: &SymbolTable,
+    ty: &Ty,
+    field: &Identifier,
+) -> Result<FieldInfo, SemanticError> {
+    let name = match ty {
+        Ty::Struct { name, .. } => name,
+        // Read only, so `ptr` can be handed to C and `len` used to loop
+        Ty::Slice { mutable, of } => {
+            let (ty, offset) = match field.as_string() {
+                "ptr" => (
+                    Ty::Pointer {
+                        mutable: *mutable,
+                        to: of.clone(),
+                    },
+                    0,
+                ),
+                "len" => (Ty::USize, Layout::slice().size / 2),
+                _ => {
+                    return Err(SemanticErrorKind::FieldNotFound {
+                        name: Identifier::new(ty.to_string()),
+                        field: field.clone(),
+                    }
+                    .into())
+                }
+            };
+            return Ok(FieldInfo {
+                name: field.clone(),
+                ty,
+                offset,
+            });
+        }
+        _ => {
+            return Err(SemanticErrorKind::FieldOnNonStruct {
+                found: ty.clone(),
+                field: field.clone(),
+            }
+            .into())
+        }
+    };
+
+    symbol_table
+        .struct_info(ty)?
+        .field(field)
+        .cloned()
+        .ok_or_else(|| {
+            SemanticErrorKind::FieldNotFound {
+                name: name.clone(),
+                field: field.clone(),
+            }
+            .into()
+        })
+}
+
+/// What an array or slice holds
+fn element_ty(ty: &Ty) -> Result<&Ty, SemanticError> {
+    match ty {
+        Ty::Array { of, .. } | Ty::Slice { of, .. } => Ok(of),
+        _ => Err(SemanticErrorKind::IndexNonArray { found: ty.clone() }.into()),
+    }
+}
+
+/// Passed on to the elements of an array literal
+fn element_hint(expected: Option<&Ty>) -> Option<&Ty> {
+    match expected {
+        Some(Ty::Array { of, .. }) => Some(of),
+        _ => None,
+    }
+}
+
+/// Any integer can index, an index known while compiling is checked right away instead of trapping
+fn visit_index(
+    index: ast::Expression,
+    ty: &Ty,
+    symbol_table: &mut SymbolTable,
+) -> Result<Expression, SemanticError> {
+    let index = index.visit_header(symbol_table)?;
+
+    if !index.ty.is_integer() {
+        return Err(
+            SemanticError::from(SemanticErrorKind::TyMismatchIndex { found: index.ty })
+                .at(index.span),
+        );
+    }
+
+    if let (Ty::Array { len, .. }, Some(value)) = (ty, constant::known_integer(&index)) {
+        if value < 0 || value >= *len as i128 {
+            return Err(SemanticError::from(SemanticErrorKind::IndexOutOfBounds {
+                index: value,
+                len: *len,
+            })
+            .at(index.span));
+        }
+    }
+
+    Ok(index)
+}
+
+/// Statics live in memory for the whole program, so they're used through a pointer
+fn static_address(
+    name: Identifier,
+    symbol_id: SymbolId,
+    info: &StaticInfo,
+    span: Span,
+) -> Expression {
+    Expression {
+        span,
+        ty: Ty::Pointer {
+            mutable: info.mutable,
+            to: Box::new(info.ty.clone()),
+        },
+        kind: ExpressionKind::StaticAddress { name, symbol_id },
+    }
+}
+
+/// What `left operator right` gives back
+fn infix_ty(
+    symbol_table: &SymbolTable,
+    operator: &InfixOperator,
+    left: &Ty,
+    right: &Ty,
+) -> Result<Ty, SemanticError> {
+    if left == right && operator.is_integer_only() && left.is_float() {
+        return Err(SemanticErrorKind::IntegerOnlyOperator {
+            operator: operator.token(),
+            ty: left.clone(),
+        }
+        .into());
+    }
+
+    let ty = match (left, right) {
+        // Offset a pointer by a number of elements, which needs their size
+        (Ty::Pointer { to, .. }, Ty::USize) if operator.is_offset() => {
+            symbol_table.ty_layout(to)?;
+            Some(left.clone())
+        }
+        // Comparisons give back a Bool, everything else keeps the operand type
+        (left, right) if left == right => operator.result_ty(left),
+        _ => None,
+    };
+
+    ty.ok_or_else(|| {
+        SemanticErrorKind::TyMismatchOperator {
+            operator: operator.clone(),
+            left: left.clone(),
+            right: right.clone(),
+        }
+        .into()
+    })
+}
+
+/// What an if without an else gives when the predicate is false
+fn unit_block(span: Span) -> Block {
+    Block {
+        span,
+        ty: Ty::Unit,
         body: Vec::new(),
         trailing: Box::new(Expression {
-            ty,
-            kind: ExpressionKind::Literal(literal),
+            span,
+            ty: Ty::Unit,
+            kind: ExpressionKind::Literal(Literal::Unit),
         }),
     }
 }
@@ -1,19 +1,48 @@
 mod block;
+mod constant;
 mod expr_misc;
 mod expression;
+pub mod monomorphize;
+mod place;
 pub mod structure;
 
-pub use {super::*, structure::*};
+pub use {super::*, std::collections::HashMap, structure::*};
 
 impl header::File {
-    pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<File, SemanticError> {
-        Ok(File {
-            items: self
-                .items
-                .into_iter()
-                .map(|i| i.visit_header(symbol_table))
-                .collect::<Result<_, _>>()?,
-        })
+    /// Generic functions come back unchecked, other modules can still call them with new arguments.
+    /// An item with an error is reported and left out
+    pub fn visit_header(
+        self,
+        symbol_table: &mut SymbolTable,
+    ) -> (Vec<Item>, Vec<header::Function>) {
+        let mut templates = Vec::new();
+        let mut items = Vec::with_capacity(self.items.len());
+
+        // Every const needs a value before any function body can use it
+        let (values, rest): (Vec<_>, Vec<_>) = self
+            .items
+            .into_iter()
+            .partition(|item| matches!(item, header::Item::Const(_) | header::Item::Static(_)));
+
+        for item in values.into_iter().chain(rest) {
+            let span = item.span();
+            let checked = match item {
+                header::Item::Const(item) => item.visit_header(symbol_table).map(|_| None),
+                header::Item::Function(func) if !func.ty_params.is_empty() => {
+                    func.resolve_names(symbol_table);
+                    templates.push(func);
+                    continue;
+                }
+                item => item.visit_header(symbol_table).map(Some),
+            };
+
+            match checked {
+                Ok(item) => items.extend(item),
+                Err(error) => symbol_table.report(error.at(span)),
+            }
+        }
+
+        (items, templates)
     }
 }
 
@@ -23,6 +52,10 @@ impl header::Item {
         Ok(match self {
             Self::Declare(declare) => Declare(declare.visit_header(symbol_table)?),
             Self::Function(func) => Function(func.visit_header(symbol_table)?),
+            Self::Struct(item) => Struct(item.visit_header()),
+            Self::Union(item) => Union(item.visit_header()),
+            Self::Static(item) => Static(item.visit_header(symbol_table)?),
+            Self::Const(_) => unreachable!("Consts are replaced by their value"),
         })
     }
 }
@@ -38,6 +71,18 @@ impl header::DeclareBlock {
                 .into_iter()
                 .map(|f| f.visit_header(symbol_table))
                 .collect::<Result<_, _>>()?,
+            statics: self
+                .statics
+                .into_iter()
+                .map(|s| DeclareStatic {
+                    mutable: s.mutable,
+                    name: s.name,
+                    ty: s.ty,
+                    symbol_id: s.symbol_id,
+                    span: s.span,
+                })
+                .collect(),
+            span: self.span,
         })
     }
 }
@@ -52,6 +97,7 @@ impl header::DeclareFunction {
             name: self.name,
             ty: self.ty,
             symbol_id: self.symbol_id,
+            span: self.span,
             params: self
                 .params
                 .into_iter()
@@ -64,27 +110,117 @@ impl header::DeclareFunction {
 impl header::Function {
     pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<Function, SemanticError> {
         let symbol_table = &mut symbol_table.fork();
+        symbol_table.set_return_ty(self.ty.clone());
+        symbol_table.set_function(self.symbol_id);
+
+        if let Some(ty) = self.self_ty {
+            symbol_table.set(Identifier::new("Self".to_string()), Symbol::new_ty(ty));
+        }
+
+        let params: Vec<_> = self
+            .params
+            .into_iter()
+            .map(|i| i.visit_header(symbol_table))
+            .collect::<Result<_, _>>()?;
+
+        // A method has to take `self` whether it needs it or not
+        for param in params.iter().filter(|p| p.name.as_string() != "self") {
+            symbol_table.watch_uses(
+                Lint::UnusedVariables,
+                param.symbol_id,
+                param.name.clone(),
+                param.span,
+            );
+        }
+
+        let body = self
+            .body
+            .visit_header_expecting(symbol_table, Some(&self.ty))?;
+
+        // The body gives back the result, unless it always returns early
+        if body.ty.join(&self.ty) != Some(self.ty.clone()) {
+            symbol_table.report(
+                SemanticError::from(SemanticErrorKind::TyMismatchReturn {
+                    expected: self.ty.clone(),
+                    found: body.ty.clone(),
+                })
+                .at(body.trailing.span),
+            );
+        }
+
         Ok(Function {
             scope: self.scope,
             name: self.name,
+            symbol_id: self.symbol_id,
+            params,
+            body,
+            ty: self.ty,
+            span: self.span,
+        })
+    }
+}
+
+impl header::Const {
+    pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<(), SemanticError> {
+        let value = constant::visit_initializer(self.value, &self.ty, symbol_table);
+        symbol_table.set_const_value(self.symbol_id, value.as_ref().ok().copied());
+        value.map(|_| ())
+    }
+}
+
+impl header::Static {
+    pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<Static, SemanticError> {
+        Ok(Static {
+            value: constant::visit_static_initializer(self.value, &self.ty, symbol_table)?,
+            scope: self.scope,
+            mutable: self.mutable,
+            name: self.name,
             ty: self.ty,
             symbol_id: self.symbol_id,
-            params: self
-                .params
-                .into_iter()
-                .map(|i| i.visit_header(symbol_table))
-                .collect::<Result<_, _>>()?,
-            body: self.body.visit_header(symbol_table)?,
+            span: self.span,
         })
     }
 }
 
+impl header::Struct {
+    pub fn visit_header(self) -> Struct {
+        Struct {
+            name: self.name,
+            args: Vec::new(),
+            fields: self.fields,
+            layout: self.layout,
+            symbol_id: self.symbol_id,
+        }
+    }
+}
+
+impl header::Union {
+    pub fn visit_header(self) -> Union {
+        Union {
+            name: self.name,
+            args: Vec::new(),
+            variants: self.variants,
+            layout: self.layout,
+            symbol_id: self.symbol_id,
+        }
+    }
+}
+
 impl header::Parameter {
     pub fn visit_header(self, symbol_table: &mut SymbolTable) -> Result<Parameter, SemanticError> {
+        let symbol_id = symbol_table.set(
+            self.name.clone(),
+            Symbol::new_local(self.ty.clone(), self.mutable),
+        );
+        if self.mutable {
+            symbol_table.declare_mutable(symbol_id, self.name.clone(), self.span);
+        }
+
         Ok(Parameter {
-            symbol_id: symbol_table.set(self.name.clone(), Symbol::new_local(self.ty)),
+            symbol_id,
             name: self.name,
             ty: self.ty,
+            span: self.span,
         })
     }
 }
@@ -0,0 +1,327 @@
+use super::*;
+
+/// Check every generic function once for each set of type arguments it was called with,
+/// templates are paired with the index of the module they're checked in
+pub fn visit_instances(
+    templates: &HashMap<SymbolId, (usize, header::Function)>,
+    symbol_tables: &mut [SymbolTable],
+) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut failed = HashSet::new();
+
+    // Checking an instance can call for more instances, so keep going until there are none
+    while let Some(instance) = symbol_tables[0].next_func_instance() {
+        // Other instances would find the same mistakes again, only with other types
+        if failed.contains(&instance.template) {
+            continue;
+        }
+
+        let symbol_id = instance.template;
+        let (module, template) = &templates[&symbol_id];
+        let symbol_table = &mut symbol_tables[*module];
+        let errors = symbol_table.error_count();
+        match template.instantiate(instance, symbol_table) {
+            Ok(func) => items.push(Item::Function(func)),
+            Err(error) => symbol_table.report(error.at(template.span)),
+        }
+        if symbol_table.error_count() > errors {
+            failed.insert(symbol_id);
+        }
+    }
+
+    items
+}
+
+/// Generic types were laid out as they were used, each one is its own type now
+pub fn visit_ty_instances(symbol_table: &mut SymbolTable) -> Vec<Item> {
+    let mut items = Vec::new();
+
+    for (ty, info) in symbol_table.take_ty_instances() {
+        items.push(match (ty, info) {
+            (
+                Ty::Struct {
+                    name,
+                    symbol_id,
+                    args,
+                },
+                TyInfo::Struct(info),
+            ) => Item::Struct(Struct {
+                name,
+                args,
+                fields: info.fields,
+                layout: info.layout,
+                symbol_id,
+            }),
+            (
+                Ty::Union {
+                    name,
+                    symbol_id,
+                    args,
+                },
+                TyInfo::Union(info),
+            ) => Item::Union(Union {
+                name,
+                args,
+                variants: info.variants,
+                layout: info.layout,
+                symbol_id,
+            }),
+            _ => unreachable!("Info matches the type"),
+        });
+    }
+
+    items
+}
+
+impl header::Function {
+    /// A copy of the function with the type parameters replaced, under its own name
+    fn instantiate(
+        &self,
+        instance: FuncInstance,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<Function, SemanticError> {
+        let substitution = self
+            .ty_params
+            .iter()
+            .cloned()
+            .zip(instance.args.iter().cloned())
+            .collect::<Substitution>();
+
+        // The body names the type parameters too, now they just mean the arguments
+        let symbol_table = &mut symbol_table.fork();
+        for (param, arg) in substitution.iter() {
+            symbol_table.set(param.clone(), Symbol::new_ty(arg.clone()));
+        }
+        for bound in self.bounds.iter() {
+            symbol_table.add_bound(substitution[&bound.param].clone(), bound.name.clone());
+        }
+
+        let params = self
+            .params
+            .iter()
+            .map(|p| header::Parameter {
+                mutable: p.mutable,
+                name: p.name.clone(),
+                ty: p.ty.substitute(&substitution),
+                span: p.span,
+            })
+            .collect::<Vec<_>>();
+        let ty = self.ty.substitute(&substitution);
+
+        header::lay_out_signature(&params, &ty, symbol_table)
+            .map_err(|error| error.at(self.span))?;
+
+        header::Function {
+            scope: self.scope,
+            name: Identifier::new(format!(
+                "{}{}",
+                self.name.as_string(),
+                TyArgs(&instance.args)
+            )),
+            ty_params: Vec::new(),
+            bounds: Vec::new(),
+            params,
+            ty,
+            self_ty: self.self_ty.as_ref().map(|ty| ty.substitute(&substitution)),
+            body: self.body.clone(),
+            symbol_id: instance.symbol_id,
+            span: self.span,
+        }
+        .visit_header(symbol_table)
+    }
+}
+
+impl header::Function {
+    /// A template is only checked once it has type arguments, but the names its body uses
+    /// don't depend on them, so one that's never called still reports the ones that don't exist
+    pub fn resolve_names(&self, symbol_table: &SymbolTable) {
+        // A method can build its own type by the name `Self`
+        let this = self
+            .self_ty
+            .as_ref()
+            .map(|_| Identifier::new("Self".to_string()));
+        let mut names = Names {
+            symbol_table,
+            locals: self
+                .params
+                .iter()
+                .map(|p| p.name.clone())
+                .chain(this)
+                .collect(),
+            labels: Vec::new(),
+        };
+        names.block(&self.body);
+    }
+}
+
+/// What's in scope at each point of a body, on top of what the symbol table has
+struct Names<'a, 't> {
+    symbol_table: &'a SymbolTable<'t>,
+    locals: Vec<Identifier>,
+    labels: Vec<Identifier>,
+}
+
+impl Names<'_, '_> {
+    fn block(&mut self, block: &ast::Block) {
+        let outer = self.locals.len();
+        for statement in block.body.iter() {
+            match statement {
+                ast::Statement::LetBinding { place, value, .. } => {
+                    self.expression(value);
+                    self.locals.push(place.clone().visit_common());
+                }
+                ast::Statement::SideEffect(value) => self.expression(value),
+            }
+        }
+        if let Some(trailing) = &block.trailing {
+            self.expression(trailing);
+        }
+        self.locals.truncate(outer);
+    }
+
+    fn lookup(
+        &self,
+        name: &ast::Identifier,
+        span: Span,
+        not_found: fn(Identifier) -> SemanticErrorKind,
+    ) {
+        let name = name.clone().visit_common();
+        if !self.locals.contains(&name) && self.symbol_table.get(&name).is_none() {
+            self.symbol_table
+                .report(SemanticError::from(not_found(name)).at(span));
+        }
+    }
+
+    fn in_loop(&mut self, label: &Option<ast::Identifier>, body: &ast::Block) {
+        let outer = self.labels.len();
+        self.labels
+            .extend(label.clone().map(ast::Identifier::visit_common));
+        self.block(body);
+        self.labels.truncate(outer);
+    }
+
+    fn expression(&mut self, expression: &ast::Expression) {
+        use ast::ExpressionKind::*;
+
+        let span = expression.span;
+        match &expression.kind {
+            Literal(_) | StringLiteral { .. } | Error => {}
+            Lookup(name) => self.lookup(name, span, |symbol| SemanticErrorKind::LocalNotFound {
+                symbol,
+            }),
+            Block(block) => self.block(block),
+            Assignment { place, value, .. } => {
+                self.expression(place);
+                self.expression(value);
+            }
+            FuncCall { name, args } => {
+                self.lookup(name, span, |symbol| SemanticErrorKind::FuncNotFound {
+                    symbol,
+                });
+                args.iter().for_each(|arg| self.expression(arg));
+            }
+            Call { callee, args } => {
+                self.expression(callee);
+                args.iter().for_each(|arg| self.expression(arg));
+            }
+            PrefixCall { value, .. }
+            | AddressOf { place: value, .. }
+            | Deref(value)
+            | Cast { value, .. }
+            | FieldAccess { value, .. }
+            | ArrayRepeat { value, .. } => self.expression(value),
+            InfixCall { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            IfElse {
+                predicate,
+                when_true,
+                when_false,
+            } => {
+                self.expression(predicate);
+                self.block(when_true);
+                if let Some(when_false) = when_false {
+                    self.block(when_false);
+                }
+            }
+            Loop { label, body } => self.in_loop(label, body),
+            While {
+                label,
+                predicate,
+                body,
+            } => {
+                self.expression(predicate);
+                self.in_loop(label, body);
+            }
+            Break { label, value } => {
+                self.label(label, span);
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Continue { label } => self.label(label, span),
+            Return { value } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            StructLiteral { name, fields } => {
+                self.lookup(name, span, |symbol| SemanticErrorKind::TyNotFound {
+                    symbol,
+                });
+                fields
+                    .iter()
+                    .for_each(|field| self.expression(&field.value));
+            }
+            ArrayLiteral { values } => values.iter().for_each(|value| self.expression(value)),
+            Index { value, index } => {
+                self.expression(value);
+                self.expression(index);
+            }
+            Slice {
+                value, start, end, ..
+            } => {
+                self.expression(value);
+                start.iter().chain(end).for_each(|end| self.expression(end));
+            }
+            VariantLiteral { name, values } => {
+                self.lookup(name, span, |symbol| SemanticErrorKind::VariantNotFound {
+                    symbol,
+                });
+                values.iter().for_each(|value| self.expression(value));
+            }
+            Match { value, arms } => {
+                self.expression(value);
+                for arm in arms.iter() {
+                    let outer = self.locals.len();
+                    if let ast::Pattern::Variant { name, bindings } = &arm.pattern {
+                        self.lookup(name, arm.span, |symbol| {
+                            SemanticErrorKind::VariantNotFound { symbol }
+                        });
+                        self.locals.extend(
+                            bindings
+                                .iter()
+                                .flatten()
+                                .cloned()
+                                .map(ast::Identifier::visit_common),
+                        );
+                    }
+                    self.expression(&arm.value);
+                    self.locals.truncate(outer);
+                }
+            }
+        }
+    }
+
+    fn label(&self, label: &Option<ast::Identifier>, span: Span) {
+        if let Some(label) = label {
+            let label = label.clone().visit_common();
+            if !self.labels.contains(&label) {
+                self.symbol_table.report(
+                    SemanticError::from(SemanticErrorKind::LabelNotFound { label }).at(span),
+                );
+            }
+        }
+    }
+}
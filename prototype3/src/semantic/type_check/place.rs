@@ -0,0 +1,260 @@
+use super::*;
+
+/// Somewhere a value can be written or have its address taken,
+/// a local or static, a field, an element, or whatever a pointer points to
+pub enum Place {
+    /// Kept out of memory until its address is taken
+    Local {
+        name: Identifier,
+        symbol_id: SymbolId,
+        ty: Ty,
+        mutable: bool,
+        span: Span,
+    },
+    /// Anything in memory is used through a pointer to it
+    Memory(Box<Expression>),
+}
+
+impl Expression {
+    /// The place this expression reads, `None` when it makes a new value like a call does,
+    /// a `mutable` place has to be writable
+    pub fn into_place(
+        self,
+        mutable: bool,
+        symbol_table: &SymbolTable,
+    ) -> Result<Option<Place>, SemanticError> {
+        let span = self.span;
+        match &self.kind {
+            // Whatever it was meant to be, it's already reported
+            ExpressionKind::Error => return Err(SemanticErrorKind::AlreadyReported.into()),
+            // A slice can't be moved or resized in place, make a new one instead
+            ExpressionKind::FieldAccess { value, field, .. }
+                if matches!(value.ty, Ty::Slice { .. }) =>
+            {
+                return Err(SemanticError::from(SemanticErrorKind::FieldOnNonStruct {
+                    found: value.ty.clone(),
+                    field: field.clone(),
+                })
+                .at(span))
+            }
+            ExpressionKind::FieldAccess { value, .. } | ExpressionKind::Index { value, .. }
+                if mutable =>
+            {
+                check_writable(value, symbol_table).map_err(|error| error.at(span))?
+            }
+            ExpressionKind::Deref(pointer) if mutable => {
+                check_writable_pointer(pointer).map_err(|error| error.at(span))?
+            }
+            ExpressionKind::Lookup { place, symbol_id } if mutable => {
+                check_mutable_local(place, *symbol_id, symbol_table)
+                    .map_err(|error| error.at(span))?
+            }
+            _ => {}
+        }
+
+        let to = self.ty;
+
+        let pointer = match self.kind {
+            ExpressionKind::Lookup { place, symbol_id } => {
+                return Ok(Some(Place::Local {
+                    name: place,
+                    symbol_id,
+                    ty: to,
+                    mutable,
+                    span,
+                }))
+            }
+
+            // Statics are read through their address too
+            ExpressionKind::Deref(pointer) => pointer.kind,
+
+            // Part of something in memory, its address is found the same way it'd be read
+            kind @ (ExpressionKind::FieldAccess { .. } | ExpressionKind::Index { .. }) => {
+                ExpressionKind::PartAddress(Box::new(Expression {
+                    span,
+                    ty: to.clone(),
+                    kind,
+                }))
+            }
+
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Place::Memory(Box::new(Expression {
+            span,
+            ty: Ty::Pointer {
+                mutable,
+                to: Box::new(to),
+            },
+            kind: pointer,
+        }))))
+    }
+}
+
+impl Place {
+    pub fn ty(&self) -> &Ty {
+        match self {
+            Self::Local { ty, .. } => ty,
+            Self::Memory(pointer) => pointer.ty.pointee().expect("Places in memory are pointers"),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Local { span, .. } => *span,
+            Self::Memory(pointer) => pointer.span,
+        }
+    }
+
+    /// `&place`, a local behind a pointer has to live in memory
+    pub fn address(self, symbol_table: &SymbolTable) -> Expression {
+        match self {
+            Self::Local {
+                name,
+                symbol_id,
+                ty,
+                mutable,
+                span,
+            } => {
+                symbol_table.mark_address_taken(symbol_id);
+                Expression {
+                    span,
+                    ty: Ty::Pointer {
+                        mutable,
+                        to: Box::new(ty),
+                    },
+                    kind: ExpressionKind::AddressOf {
+                        place: name,
+                        symbol_id,
+                    },
+                }
+            }
+            Self::Memory(pointer) => *pointer,
+        }
+    }
+
+    /// `place = value`, or `place op= value` which only works out where the place is once
+    pub fn assign(self, operator: Option<InfixOperator>, value: Expression) -> Expression {
+        let span = self.span();
+        let kind = match self {
+            Self::Local {
+                name,
+                symbol_id,
+                ty,
+                span,
+                ..
+            } => {
+                let value = match operator {
+                    Some(operator) => Expression {
+                        span,
+                        ty: ty.clone(),
+                        kind: ExpressionKind::InfixCall {
+                            left: Box::new(Expression {
+                                span,
+                                ty,
+                                kind: ExpressionKind::Lookup {
+                                    place: name.clone(),
+                                    symbol_id,
+                                },
+                            }),
+                            operator,
+                            right: Box::new(value),
+                        },
+                    },
+                    None => value,
+                };
+
+                ExpressionKind::Assignment {
+                    place: name,
+                    value: Box::new(value),
+                    symbol_id,
+                }
+            }
+            Self::Memory(pointer) => match operator {
+                Some(operator) => ExpressionKind::CompoundAssignment {
+                    pointer,
+                    operator,
+                    value: Box::new(value),
+                },
+                None => ExpressionKind::DerefAssignment {
+                    pointer,
+                    value: Box::new(value),
+                },
+            },
+        };
+
+        Expression {
+            span,
+            ty: Ty::Unit,
+            kind,
+        }
+    }
+}
+
+/// Writing a field of a struct behind a pointer needs that pointer to be `*mut`, and a slice to be `[mut T]`,
+/// while a struct or array held in a local needs the local to be `mut`
+pub fn check_writable(
+    target: &Expression,
+    symbol_table: &SymbolTable,
+) -> Result<(), SemanticError> {
+    match target.ty {
+        Ty::Slice { mutable: false, .. } => {
+            return Err(SemanticErrorKind::AssignThroughConstSlice {
+                found: target.ty.clone(),
+            }
+            .into())
+        }
+        // Its elements are somewhere else, the slice itself doesn't change
+        Ty::Slice { mutable: true, .. } => return Ok(()),
+        _ => {}
+    }
+
+    match &target.kind {
+        ExpressionKind::FieldAccess { value, .. } => check_writable(value, symbol_table),
+        ExpressionKind::Index { value, .. } => check_writable(value, symbol_table),
+        ExpressionKind::Deref(pointer) => check_writable_pointer(pointer),
+        ExpressionKind::Lookup { place, symbol_id } => {
+            check_mutable_local(place, *symbol_id, symbol_table)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_mutable_local(
+    name: &Identifier,
+    symbol_id: SymbolId,
+    symbol_table: &SymbolTable,
+) -> Result<(), SemanticError> {
+    let mutable = symbol_table
+        .get(name)
+        .and_then(Symbol::as_local)
+        .is_some_and(|info| info.mutable);
+    if !mutable {
+        return Err(SemanticErrorKind::ImmutableLocal {
+            symbol: name.clone(),
+        }
+        .into());
+    }
+
+    symbol_table.mark_mutated(symbol_id);
+    Ok(())
+}
+
+/// Only a `static mut` can be written, and anything else only through a `*mut`
+fn check_writable_pointer(pointer: &Expression) -> Result<(), SemanticError> {
+    match (&pointer.kind, &pointer.ty) {
+        (ExpressionKind::StaticAddress { name, .. }, Ty::Pointer { mutable: false, .. }) => {
+            Err(SemanticErrorKind::ImmutableStatic {
+                symbol: name.clone(),
+            }
+            .into())
+        }
+        (_, Ty::Pointer { mutable: false, .. }) => {
+            Err(SemanticErrorKind::AssignThroughConstPointer {
+                found: pointer.ty.clone(),
+            }
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
@@ -1,19 +1,36 @@
-use super::*;
+use {super::*, std::collections::HashSet};
 
 #[derive(Debug, Clone)]
 pub struct File {
     pub items: Vec<Item>,
+    /// Locals that have to live on the stack instead of in registers
+    pub address_taken: HashSet<SymbolId>,
+    pub warnings: Vec<SemanticWarning>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Item {
     Declare(DeclareBlock),
     Function(Function),
+    Struct(Struct),
+    Union(Union),
+    Static(Static),
 }
 
 #[derive(Debug, Clone)]
 pub struct DeclareBlock {
     pub functions: Vec<DeclareFunction>,
+    pub statics: Vec<DeclareStatic>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeclareStatic {
+    pub mutable: bool,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub symbol_id: SymbolId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +39,7 @@ pub struct DeclareFunction {
     pub params: Vec<Parameter>,
     pub ty: Ty,
     pub symbol_id: SymbolId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +50,59 @@ pub struct Function {
     pub ty: Ty,
     pub body: Block,
     pub symbol_id: SymbolId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub scope: Scope,
+    /// Immutable statics go in read only memory
+    pub mutable: bool,
+    pub name: Identifier,
+    pub ty: Ty,
+    pub value: Constant,
+    pub symbol_id: SymbolId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name: Identifier,
+    /// Only given for instances of a generic struct
+    pub args: Vec<Ty>,
+    pub fields: Vec<FieldInfo>,
+    pub layout: Layout,
+    pub symbol_id: SymbolId,
+}
+
+impl Struct {
+    pub fn ty(&self) -> Ty {
+        Ty::Struct {
+            name: self.name.clone(),
+            symbol_id: self.symbol_id,
+            args: self.args.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Union {
+    pub name: Identifier,
+    /// Only given for instances of a generic union
+    pub args: Vec<Ty>,
+    pub variants: Vec<VariantInfo>,
+    pub layout: Layout,
+    pub symbol_id: SymbolId,
+}
+
+impl Union {
+    pub fn ty(&self) -> Ty {
+        Ty::Union {
+            name: self.name.clone(),
+            symbol_id: self.symbol_id,
+            args: self.args.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +110,7 @@ pub struct Parameter {
     pub name: Identifier,
     pub ty: Ty,
     pub symbol_id: SymbolId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -46,11 +118,13 @@ pub struct Block {
     pub body: Vec<Statement>,
     pub trailing: Box<Expression>,
     pub ty: Ty,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     LetBinding {
+        span: Span,
         place: Identifier,
         ty: Ty,
         value: Expression,
@@ -59,10 +133,30 @@ pub enum Statement {
     SideEffect(Expression),
 }
 
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::LetBinding { span, .. } => *span,
+            Statement::SideEffect(value) => value.span,
+        }
+    }
+
+    /// Jumps away before the statement after it can run
+    pub fn diverges(&self) -> bool {
+        match self {
+            Statement::LetBinding { value, .. } | Statement::SideEffect(value) => {
+                value.ty == Ty::Never
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Expression {
     pub kind: ExpressionKind,
     pub ty: Ty,
+    /// Where the source expression was written, made up ones share the span they came from
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -83,6 +177,31 @@ pub enum ExpressionKind {
         args: Vec<Expression>,
         symbol_id: SymbolId,
     },
+    /// Calls a C function declared with `...`, the args past its params are already promoted
+    CallVariadic {
+        args: Vec<Expression>,
+        symbol_id: SymbolId,
+    },
+    /// Calls through a function value, the signature comes from its type
+    CallIndirect {
+        callee: Box<Expression>,
+        args: Vec<Expression>,
+    },
+    /// A function used as a value
+    FuncAddress {
+        symbol_id: SymbolId,
+    },
+    /// A pointer and the vtable holding these methods, in the order of their trait
+    MakeDyn {
+        pointer: Box<Expression>,
+        methods: Vec<SymbolId>,
+    },
+    /// Calls the method at `index` in the vtable, passing the object's pointer as `self`
+    CallDyn {
+        object: Box<Expression>,
+        index: u32,
+        args: Vec<Expression>,
+    },
     PrefixCall {
         operator: PrefixOperator,
         value: Box<Expression>,
@@ -97,6 +216,131 @@ pub enum ExpressionKind {
         when_true: Block,
         when_false: Block,
     },
+    Loop {
+        body: Block,
+        symbol_id: SymbolId,
+    },
+    While {
+        predicate: Box<Expression>,
+        body: Block,
+        symbol_id: SymbolId,
+    },
+    Break {
+        value: Box<Expression>,
+        symbol_id: SymbolId,
+    },
+    Continue {
+        symbol_id: SymbolId,
+    },
+    Return {
+        value: Box<Expression>,
+    },
+    AddressOf {
+        place: Identifier,
+        symbol_id: SymbolId,
+    },
+    /// Where a field or element is, to write it or take its address
+    PartAddress(Box<Expression>),
+    /// Statics are used through their address, like anything else in memory
+    StaticAddress {
+        name: Identifier,
+        symbol_id: SymbolId,
+    },
+    /// Either a `Str` or a pointer to the first byte, the data always ends in a NUL
+    StringLiteral {
+        bytes: Vec<u8>,
+        nul_terminated: bool,
+    },
+    Deref(Box<Expression>),
+    /// Only between numeric types, the target is the type of the expression
+    Cast {
+        value: Box<Expression>,
+        trapping: bool,
+    },
+    DerefAssignment {
+        pointer: Box<Expression>,
+        value: Box<Expression>,
+    },
+    /// `*pointer op= value`, the pointer is only worked out once
+    CompoundAssignment {
+        pointer: Box<Expression>,
+        operator: InfixOperator,
+        value: Box<Expression>,
+    },
+    StructLiteral {
+        fields: Vec<FieldInit>,
+        symbol_id: SymbolId,
+    },
+    FieldAccess {
+        value: Box<Expression>,
+        field: Identifier,
+        offset: u32,
+    },
+    VariantLiteral {
+        tag: u32,
+        fields: Vec<FieldInit>,
+    },
+    ArrayLiteral {
+        values: Vec<Expression>,
+    },
+    /// Every element gets a copy of the one value
+    ArrayRepeat {
+        value: Box<Expression>,
+    },
+    /// Traps when out of bounds, unless compiled with `unchecked_indexing`
+    Index {
+        value: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// Part of an array or slice, a missing end is the length
+    Slice {
+        value: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
+    Match {
+        value: Box<Expression>,
+        arms: Vec<MatchArm>,
+        /// How many variants the tag can pick from
+        variants: u32,
+    },
+    /// Left where the source had an error, so it never reaches the backend
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub value: Expression,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Variant { tag: u32, bindings: Vec<Binding> },
+}
+
+/// A local pulled out of the payload
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub symbol_id: SymbolId,
+    pub ty: Ty,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    pub name: Identifier,
+    pub value: Expression,
+    pub offset: u32,
+}
+
+/// What a static starts as, arrays and structs are built from their constant parts
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Scalar(Literal),
+    /// Each part with its offset from the start, anything between them is padding
+    Aggregate(Vec<(u32, Constant)>),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -113,6 +357,8 @@ pub enum Literal {
     USize(usize),
     F32(f32),
     F64(f64),
+    Bool(bool),
+    Unit,
 }
 
 impl From<Literal> for Ty {
@@ -132,6 +378,8 @@ impl From<Literal> for Ty {
             USize(_) => Self::USize,
             F32(_) => Self::F32,
             F64(_) => Self::F64,
+            Bool(_) => Self::Bool,
+            Unit => Self::Unit,
         }
     }
 }
@@ -139,6 +387,8 @@ impl From<Literal> for Ty {
 #[derive(Debug, Clone)]
 pub enum PrefixOperator {
     Negate,
+    BooleanNot,
+    BitNot,
 }
 
 #[derive(Debug, Clone)]
@@ -147,6 +397,13 @@ pub enum InfixOperator {
     Subtract,
     Multiply,
     Divide,
+    Remainder,
+
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 
     Equal,
     NotEqual,
@@ -154,4 +411,106 @@ pub enum InfixOperator {
     LessThan,
     GreaterOrEqual,
     LessOrEqual,
+
+    BooleanAnd,
+    BooleanOr,
+}
+
+impl PrefixOperator {
+    /// What the operator gives back for a value of `ty`, if it can use it at all
+    pub fn result_ty(&self, ty: &Ty) -> Option<Ty> {
+        use PrefixOperator::*;
+        match self {
+            Negate if ty.is_numeric() => Some(ty.clone()),
+            BooleanNot if *ty == Ty::Bool => Some(Ty::Bool),
+            BitNot if ty.is_integer() => Some(ty.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer_only(&self) -> bool {
+        matches!(self, PrefixOperator::BitNot)
+    }
+
+    pub fn token(&self) -> &'static str {
+        use PrefixOperator::*;
+        match self {
+            Negate => "-",
+            BooleanNot => "!",
+            BitNot => "~",
+        }
+    }
+}
+
+impl InfixOperator {
+    /// What the operator gives back for two operands of `ty`, if it can use them at all
+    pub fn result_ty(&self, ty: &Ty) -> Option<Ty> {
+        use InfixOperator::*;
+        match self {
+            Add | Subtract | Multiply | Divide if ty.is_numeric() => Some(ty.clone()),
+            Remainder | ShiftLeft | ShiftRight if ty.is_integer() => Some(ty.clone()),
+            BitAnd | BitOr | BitXor if ty.is_integer() || *ty == Ty::Bool => Some(ty.clone()),
+            Equal | NotEqual if !ty.is_aggregate() => Some(Ty::Bool),
+            GreaterThan | LessThan | GreaterOrEqual | LessOrEqual
+                if ty.is_numeric() || ty.is_pointer() =>
+            {
+                Some(Ty::Bool)
+            }
+            BooleanAnd | BooleanOr if *ty == Ty::Bool => Some(Ty::Bool),
+            _ => None,
+        }
+    }
+
+    /// Operators that can move a pointer by some elements
+    pub fn is_offset(&self) -> bool {
+        matches!(self, InfixOperator::Add | InfixOperator::Subtract)
+    }
+
+    /// Operators that have no meaning for floating point
+    pub fn is_integer_only(&self) -> bool {
+        use InfixOperator::*;
+        matches!(
+            self,
+            Remainder | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight
+        )
+    }
+
+    pub fn token(&self) -> &'static str {
+        use InfixOperator::*;
+        match self {
+            Add => "+",
+            Subtract => "-",
+            Multiply => "*",
+            Divide => "/",
+            Remainder => "%",
+
+            BitAnd => "&",
+            BitOr => "|",
+            BitXor => "^",
+            ShiftLeft => "<<",
+            ShiftRight => ">>",
+
+            Equal => "==",
+            NotEqual => "!=",
+            GreaterThan => ">",
+            LessThan => "<",
+            GreaterOrEqual => ">=",
+            LessOrEqual => "<=",
+
+            BooleanAnd => "&&",
+            BooleanOr => "||",
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        use InfixOperator::*;
+        matches!(
+            self,
+            Equal | NotEqual | GreaterThan | LessThan | GreaterOrEqual | LessOrEqual
+        )
+    }
+
+    pub fn is_short_circuit(&self) -> bool {
+        matches!(self, InfixOperator::BooleanAnd | InfixOperator::BooleanOr)
+    }
 }
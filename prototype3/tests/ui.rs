@@ -0,0 +1,184 @@
+//! Every `.son` file in `tests/ui` is compiled, and the errors and warnings it gives
+//! have to match the `.stderr` file next to it, which is left out when there are none.
+//!
+//! A file can start with lines of options for the test:
+//! - `// flags: --deny shadowing` are passed to the compiler like on the command line
+//! - `// exit: 25` links it with `cc` and runs it, it has to exit with that code
+//!   and print what the `.stdout` file next to it has. A `.c` file next to it is linked in too
+//!
+//! Run with `BLESS=1` to write what each test gives as its expected output instead.
+
+use {
+    semantic::{Lint, LintLevel},
+    sonancelang_prototype3::{compile, diagnostic::SourceMap, semantic, CompileOptions},
+    std::{
+        env,
+        fs::{self, read_to_string},
+        path::{Path, PathBuf},
+        process::Command,
+    },
+};
+
+const UI: &str = "tests/ui";
+
+#[test]
+fn ui() {
+    let mut fixtures = fs::read_dir(UI)
+        .expect("Fixture directory exists")
+        .map(|entry| entry.expect("Fixture can be listed").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "son"))
+        .collect::<Vec<_>>();
+    fixtures.sort();
+
+    let bless = env::var_os("BLESS").is_some();
+    let failures = fixtures
+        .iter()
+        .filter_map(|path| run(path, bless).err())
+        .collect::<Vec<_>>();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} fixtures failed\n\n{}",
+        failures.len(),
+        fixtures.len(),
+        failures.join("\n")
+    );
+}
+
+struct Directives {
+    options: CompileOptions,
+    exit: Option<i32>,
+}
+
+/// Compiles and maybe runs one fixture, giving back what didn't match
+fn run(path: &Path, bless: bool) -> Result<(), String> {
+    let name = path.display().to_string();
+    let input = read_to_string(path).map_err(|error| format!("{}: {}", name, error))?;
+    let directives = directives(&input).map_err(|error| format!("{}: {}", name, error))?;
+
+    let mut sources = SourceMap::default();
+    let entry = sources.add(path.to_path_buf(), input);
+
+    let (stderr, binary) = match compile(&mut sources, entry, &directives.options) {
+        Ok(compiled) => (compiled.render_warnings(&sources), Some(compiled.binary)),
+        Err(error) => (error.render(&sources), None),
+    };
+    expect(&path.with_extension("stderr"), &stderr, bless)?;
+
+    let exit = match directives.exit {
+        Some(exit) => exit,
+        None => return Ok(()),
+    };
+    let binary = binary.ok_or_else(|| format!("{}: expected to compile\n{}", name, stderr))?;
+    let (code, stdout) = link_and_run(path, &binary)?;
+    expect(&path.with_extension("stdout"), &stdout, bless)?;
+
+    if code != Some(exit) {
+        return Err(format!(
+            "{}: expected exit {}, got {:?}\n",
+            name, exit, code
+        ));
+    }
+    Ok(())
+}
+
+fn directives(input: &str) -> Result<Directives, String> {
+    let mut options = CompileOptions {
+        module_path: vec![PathBuf::from(UI)],
+        ..CompileOptions::default()
+    };
+    let mut exit = None;
+
+    for line in input.lines().map_while(|line| line.strip_prefix("// ")) {
+        if let Some(code) = line.strip_prefix("exit: ") {
+            exit = Some(
+                code.parse()
+                    .map_err(|_| format!("Bad exit code `{}`", code))?,
+            );
+        } else if let Some(flags) = line.strip_prefix("flags: ") {
+            let mut flags = flags.split_whitespace();
+            while let Some(flag) = flags.next() {
+                let level = match flag {
+                    "--legacy-truthiness" => {
+                        options.legacy_truthiness = true;
+                        continue;
+                    }
+                    "--unchecked-indexing" => {
+                        options.unchecked_indexing = true;
+                        continue;
+                    }
+                    "--allow" => LintLevel::Allow,
+                    "--warn" => LintLevel::Warn,
+                    "--deny" => LintLevel::Deny,
+                    _ => return Err(format!("Unknown flag `{}`", flag)),
+                };
+                let lint = flags
+                    .next()
+                    .ok_or_else(|| format!("`{}` needs a lint", flag))?
+                    .parse::<Lint>()?;
+                options.lint_levels.insert(lint, level);
+            }
+        }
+    }
+
+    Ok(Directives { options, exit })
+}
+
+fn link_and_run(path: &Path, binary: &[u8]) -> Result<(Option<i32>, String), String> {
+    let name = path.display().to_string();
+    let stem = path.file_stem().expect("Fixtures have a name");
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join(stem);
+    let object = out.with_extension("o");
+    fs::write(&object, binary).map_err(|error| format!("{}: {}", name, error))?;
+
+    let c_source = path.with_extension("c");
+    let linked = Command::new("cc")
+        .arg("-no-pie")
+        .arg("-o")
+        .arg(&out)
+        .arg(&object)
+        .args(Some(&c_source).filter(|source| source.exists()))
+        .output()
+        .map_err(|error| format!("{}: can't run cc, {}", name, error))?;
+    if !linked.status.success() {
+        return Err(format!(
+            "{}: linking failed\n{}",
+            name,
+            String::from_utf8_lossy(&linked.stderr)
+        ));
+    }
+
+    let ran = Command::new(&out)
+        .output()
+        .map_err(|error| format!("{}: {}", name, error))?;
+    Ok((
+        ran.status.code(),
+        String::from_utf8_lossy(&ran.stdout).into_owned(),
+    ))
+}
+
+/// An empty expected file is the same as none at all
+fn expect(path: &Path, found: &str, bless: bool) -> Result<(), String> {
+    if bless {
+        let written = if found.is_empty() {
+            fs::remove_file(path).or_else(|error| match error.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(error),
+            })
+        } else {
+            fs::write(path, found)
+        };
+        return written.map_err(|error| format!("{}: {}", path.display(), error));
+    }
+
+    let expected = read_to_string(path).unwrap_or_default();
+    if expected != found {
+        return Err(format!(
+            "{}: output differs\n--- expected\n{}--- found\n{}",
+            path.display(),
+            expected,
+            found
+        ));
+    }
+    Ok(())
+}
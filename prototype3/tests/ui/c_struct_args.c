@@ -0,0 +1,25 @@
+#include <stdio.h>
+
+struct Pad { char a; long b; };
+struct Big { long a, b, c; };
+struct Pair { double x, y; };
+
+int from_sonance(int a, int b, int c, int d, int e, struct Pad p, int f);
+long big_from_sonance(struct Big big, int x);
+
+int pad(int a, int b, int c, int d, int e, struct Pad p, int f) {
+    printf("%d %d %d %d %d %d %ld %d\n", a, b, c, d, e, p.a, p.b, f);
+    return from_sonance(a, b, c, d, e, p, f);
+}
+
+long big(struct Big big, int x) {
+    printf("%ld %ld %ld %d\n", big.a, big.b, big.c, x);
+    struct Big back = { big.c, big.b, big.a };
+    return big_from_sonance(back, x);
+}
+
+double pair(double a, double b, double c, double d, double e, double f, double g,
+            struct Pair p, double h) {
+    printf("%g %g %g %g %g %g %g %g %g %g\n", a, b, c, d, e, f, g, p.x, p.y, h);
+    return p.x + p.y + h;
+}